@@ -1,4 +1,7 @@
 //! The C interface of the `arducam_mipicamera` library.
+//!
+//! With the `dlopen` feature, the functions panic if the library is not loaded
+//! and can't be found; see `load_library`.
 
 use std::os::raw::{c_char, c_int, c_void};
 
@@ -8,6 +11,9 @@ pub const IMAGE_ENCODING_RAW_BAYER: u32 = u32::from_le_bytes(*b"RAW ");
 pub const IMAGE_ENCODING_BMP: u32 = u32::from_le_bytes(*b"BMP ");
 pub const IMAGE_ENCODING_PNG: u32 = u32::from_le_bytes(*b"PNG'");
 
+pub const VIDEO_ENCODING_H264: u32 = u32::from_le_bytes(*b"H264");
+pub const VIDEO_ENCODING_MJPEG: u32 = u32::from_le_bytes(*b"MJPG");
+
 pub const OUTPUT_FLAG_KEEP_BUFFER_REQUIREMENTS: u32 = 8;
 pub const OUTPUT_FLAG_BUFFER_ALLOCATION_USE_MMAL_CORE: u32 = 16;
 
@@ -67,9 +73,25 @@ pub const V4L2_CTRL_CLASS_USER: u32 = 9961472;
 pub const V4L2_CID_BASE: u32 = 9963776;
 pub const V4L2_CID_ARDUCAM_BASE: u32 = 9967872;
 pub const V4L2_CID_ARDUCAM_EXT_TRI: u32 = 9967873;
+pub const V4L2_CID_BRIGHTNESS: u32 = 9963776;
+pub const V4L2_CID_CONTRAST: u32 = 9963777;
+pub const V4L2_CID_SATURATION: u32 = 9963778;
+pub const V4L2_CID_HUE: u32 = 9963779;
+pub const V4L2_CID_AUTO_WHITE_BALANCE: u32 = 9963788;
+pub const V4L2_CID_RED_BALANCE: u32 = 9963790;
+pub const V4L2_CID_BLUE_BALANCE: u32 = 9963791;
+pub const V4L2_CID_GAMMA: u32 = 9963792;
+pub const V4L2_CID_EXPOSURE: u32 = 9963793;
+pub const V4L2_CID_AUTOGAIN: u32 = 9963794;
 pub const V4L2_CID_VFLIP: u32 = 9963797;
 pub const V4L2_CID_HFLIP: u32 = 9963796;
 pub const V4L2_CID_GAIN: u32 = 9963795;
+pub const V4L2_CID_SHARPNESS: u32 = 9963803;
+pub const V4L2_CTRL_CLASS_CAMERA: u32 = 10092544;
+pub const V4L2_CID_CAMERA_CLASS_BASE: u32 = 10094848;
+pub const V4L2_CID_EXPOSURE_AUTO: u32 = 10094849;
+/// Exposure time, in units of 100 µs.
+pub const V4L2_CID_EXPOSURE_ABSOLUTE: u32 = 10094850;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -147,8 +169,31 @@ pub struct Buffer {
     pub userdata: *mut c_void,
 }
 
+impl Buffer {
+    /// The used bytes of the buffer.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be null or point to `length` bytes that stay valid while `self` is borrowed.
+    pub(crate) unsafe fn bytes(&self) -> &[u8] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(self.data, self.length as usize)
+        }
+    }
+
+    /// The presentation timestamp, or `None` if it is [`TIME_UNKNOWN`].
+    pub(crate) fn timestamp(&self) -> Option<i64> {
+        match self.pts {
+            TIME_UNKNOWN => None,
+            t => Some(t),
+        }
+    }
+}
+
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Fract {
     pub numerator: u32,
     pub denominator: u32,
@@ -166,6 +211,12 @@ pub struct Format {
     pub reserved: [u32; 4],
 }
 
+// Format is plain data: the description pointer is only dereferenced by unsafe
+// code, which has to make sure it is valid regardless of the thread it runs on.
+// This allows frames to carry their Format to other threads.
+unsafe impl Send for Format {}
+unsafe impl Sync for Format {}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct CameraCtrl {
@@ -193,6 +244,23 @@ pub struct CameraInterface {
 pub type OutputCallback = unsafe extern "C" fn(buffer: *mut Buffer) -> c_int;
 pub type CameraInstance = *mut c_void;
 
+#[cfg(feature = "simulator")]
+pub use crate::simulator::ffi::*;
+
+#[cfg(all(feature = "dlopen", not(feature = "simulator")))]
+pub use crate::dynamic::{
+    arducam_capture, arducam_close_camera, arducam_get_control, arducam_get_format,
+    arducam_get_gain, arducam_get_support_controls, arducam_get_support_formats,
+    arducam_init_camera, arducam_init_camera2, arducam_manual_set_awb_compensation,
+    arducam_read_sensor_reg, arducam_release_buffer, arducam_reset_control, arducam_set_control,
+    arducam_set_lens_table, arducam_set_mode, arducam_set_raw_callback, arducam_set_resolution,
+    arducam_set_video_callback, arducam_set_yuv_callback, arducam_software_auto_exposure,
+    arducam_software_auto_white_balance, arducam_start_preview, arducam_start_preview_fix_lens,
+    arducam_stop_preview, arducam_unpack_raw10_to_raw16, arducam_unpack_raw10_to_raw8,
+    arducam_write_sensor_reg,
+};
+
+#[cfg(not(any(feature = "simulator", feature = "dlopen")))]
 #[link(name = "arducam_mipicamera")]
 extern "C" {
     /// Initialize camera.
@@ -259,13 +327,14 @@ extern "C" {
     /// - `camera_instance`: Type CameraInstance, Obtained from arducam_init_camera function.
     /// - `encoder_state`: Used to specify encoding parameters. Use default parameters if NULL.
     /// - `callback`: Callback method, this method will be called when there is data return.
+    ///   Passing NULL disables the output again.
     /// - `userdata`: Userdata, which will be a member of the buffer parameter in the callback function.
     ///
     /// Returns: error code, 0 success, !0 error.
     pub fn arducam_set_video_callback(
         camera_instance: CameraInstance,
         encoder_state: *mut VideoEncoderState,
-        callback: OutputCallback,
+        callback: Option<OutputCallback>,
         userdata: *mut c_void,
     ) -> c_int;
 
@@ -273,12 +342,13 @@ extern "C" {
     ///
     /// - `camera_instance`: Type CameraInstance, Obtained from arducam_init_camera function.
     /// - `callback`: Callback method, this method will be called when there is data return.
+    ///   Passing NULL disables the output again.
     /// - `userdata`: Userdata, which will be a member of the buffer parameter in the callback function.
     ///
     /// Returns: error code, 0 success, !0 error.
     pub fn arducam_set_raw_callback(
         camera_instance: CameraInstance,
-        callback: OutputCallback,
+        callback: Option<OutputCallback>,
         userdata: *mut c_void,
     ) -> c_int;
 
@@ -286,12 +356,13 @@ extern "C" {
     ///
     /// - `camera_instance`: Type CameraInstance, Obtained from arducam_init_camera function.
     /// - `callback`: Callback method, this method will be called when there is data return.
+    ///   Passing NULL disables the output again.
     /// - `userdata`: Userdata, which will be a member of the buffer parameter in the callback function.
     ///
     /// Returns: error code, 0 success, !0 error.
     pub fn arducam_set_yuv_callback(
         camera_instance: CameraInstance,
-        callback: OutputCallback,
+        callback: Option<OutputCallback>,
         userdata: *mut c_void,
     ) -> c_int;
 
@@ -0,0 +1,45 @@
+//! Glue between the C output callbacks and Rust closures.
+
+use crate::c;
+use std::os::raw::{c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+type Handler = Box<dyn FnMut(&c::Buffer) + Send>;
+
+/// A closure registered as userdata of one of the C output callbacks.
+///
+/// The closure is kept alive until this object is dropped, which must only
+/// happen after the callback has been unregistered in the C library.
+pub(crate) struct Registration {
+    handler: *mut Handler,
+}
+
+impl Registration {
+    pub(crate) fn new(f: impl FnMut(&c::Buffer) + Send + 'static) -> Self {
+        let handler: Handler = Box::new(f);
+        Self {
+            handler: Box::into_raw(Box::new(handler)),
+        }
+    }
+
+    /// The userdata pointer to pass along with [`trampoline`].
+    pub(crate) fn userdata(&self) -> *mut c_void {
+        self.handler as *mut c_void
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(self.handler)) };
+    }
+}
+
+/// The [`OutputCallback`][c::OutputCallback] that forwards to the [`Registration`] in the userdata.
+pub(crate) unsafe extern "C" fn trampoline(buffer: *mut c::Buffer) -> c_int {
+    let buffer = &*buffer;
+    let handler = &mut *(buffer.userdata as *mut Handler);
+    match catch_unwind(AssertUnwindSafe(|| handler(buffer))) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
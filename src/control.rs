@@ -0,0 +1,243 @@
+//! Camera controls.
+
+use crate::{c, to_result, Camera, Error, ErrorKind, Result};
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::time::Duration;
+
+macro_rules! controls {
+    ($($(#[$doc:meta])* $name:ident = $id:path,)*) => {
+        /// A camera control.
+        ///
+        /// Converts from and to the raw `i32` control id.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Control {
+            $($(#[$doc])* $name,)*
+            /// Any other control, by its raw id.
+            Other(i32),
+        }
+
+        impl Control {
+            /// Look up the control with the given raw id.
+            pub fn from_id(id: i32) -> Self {
+                $(
+                    if id == $id as i32 {
+                        return Control::$name;
+                    }
+                )*
+                Control::Other(id)
+            }
+
+            /// The raw id of this control.
+            pub fn id(self) -> i32 {
+                match self {
+                    $(Control::$name => $id as i32,)*
+                    Control::Other(id) => id,
+                }
+            }
+        }
+    };
+}
+
+controls! {
+    /// Brightness.
+    Brightness = c::V4L2_CID_BRIGHTNESS,
+    /// Contrast.
+    Contrast = c::V4L2_CID_CONTRAST,
+    /// Color saturation.
+    Saturation = c::V4L2_CID_SATURATION,
+    /// Hue.
+    Hue = c::V4L2_CID_HUE,
+    /// Automatic white balance (boolean).
+    AutoWhiteBalance = c::V4L2_CID_AUTO_WHITE_BALANCE,
+    /// Red chroma balance.
+    RedBalance = c::V4L2_CID_RED_BALANCE,
+    /// Blue chroma balance.
+    BlueBalance = c::V4L2_CID_BLUE_BALANCE,
+    /// Gamma adjust.
+    Gamma = c::V4L2_CID_GAMMA,
+    /// Exposure, in sensor specific units (usually lines).
+    Exposure = c::V4L2_CID_EXPOSURE,
+    /// Automatic gain (boolean).
+    AutoGain = c::V4L2_CID_AUTOGAIN,
+    /// Gain.
+    Gain = c::V4L2_CID_GAIN,
+    /// Horizontal flip (boolean).
+    HFlip = c::V4L2_CID_HFLIP,
+    /// Vertical flip (boolean).
+    VFlip = c::V4L2_CID_VFLIP,
+    /// Sharpness.
+    Sharpness = c::V4L2_CID_SHARPNESS,
+    /// Automatic exposure mode.
+    ExposureAuto = c::V4L2_CID_EXPOSURE_AUTO,
+    /// Exposure time, in units of 100 µs.
+    ExposureAbsolute = c::V4L2_CID_EXPOSURE_ABSOLUTE,
+    /// ArduCAM external trigger mode (boolean).
+    ArducamExternalTrigger = c::V4L2_CID_ARDUCAM_EXT_TRI,
+}
+
+impl From<i32> for Control {
+    fn from(id: i32) -> Self {
+        Control::from_id(id)
+    }
+}
+
+impl From<Control> for i32 {
+    fn from(control: Control) -> i32 {
+        control.id()
+    }
+}
+
+/// A control supported by the camera, as returned by [`Camera::supported_controls`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlInfo {
+    /// The control id.
+    pub id: i32,
+    /// Human readable name of the control.
+    pub name: String,
+    /// The minimum value.
+    pub min: i32,
+    /// The maximum value.
+    pub max: i32,
+    /// The default value.
+    pub default: i32,
+}
+
+impl ControlInfo {
+    /// The control.
+    pub fn control(&self) -> Control {
+        Control::from_id(self.id)
+    }
+
+    /// Check if the value is within the range of this control.
+    pub fn contains(&self, value: i32) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    /// Return an [`InvalidArgument`][ErrorKind::InvalidArgument] error for `operation`
+    /// if the value is not within the range of this control.
+    pub fn check(&self, value: i32, operation: &'static str) -> Result<()> {
+        if self.contains(value) {
+            Ok(())
+        } else {
+            Err(Error::with_message(
+                ErrorKind::InvalidArgument,
+                operation,
+                format!(
+                    "value {} is out of range {}..={} of control {:?} ({:#x})",
+                    value, self.min, self.max, self.name, self.id
+                ),
+            ))
+        }
+    }
+
+    /// Convert a [`CameraCtrl`][c::CameraCtrl] returned by the library.
+    ///
+    /// # Safety
+    ///
+    /// `ctrl.desc` must be null or point to a valid nul-terminated string.
+    pub(crate) unsafe fn from_raw(ctrl: &c::CameraCtrl) -> Self {
+        let name = if ctrl.desc.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(ctrl.desc).to_string_lossy().into_owned()
+        };
+        Self {
+            id: ctrl.id,
+            name,
+            min: ctrl.min_value,
+            max: ctrl.max_value,
+            default: ctrl.default_value,
+        }
+    }
+}
+
+impl Camera {
+    /// Get all controls supported by the camera, with their ranges.
+    pub fn supported_controls(&mut self) -> Result<Vec<ControlInfo>> {
+        let mut controls = Vec::new();
+        for index in 0.. {
+            let mut ctrl = MaybeUninit::uninit();
+            let r = unsafe { c::arducam_get_support_controls(self.ptr, ctrl.as_mut_ptr(), index) };
+            if r != 0 {
+                if index == 0 {
+                    to_result(r, "arducam_get_support_controls")?;
+                }
+                break;
+            }
+            // The library sets the description to a static string.
+            controls.push(unsafe { ControlInfo::from_raw(&ctrl.assume_init()) });
+        }
+        Ok(controls)
+    }
+
+    /// Set a camera control to the specified value, after checking it against the supported range.
+    ///
+    /// Returns an error of kind [`Unsupported`][ErrorKind::Unsupported] if the camera does not
+    /// support the control, or [`InvalidArgument`][ErrorKind::InvalidArgument] if the value is out
+    /// of range, without forwarding the value to the sensor.
+    pub fn set_control_checked(&mut self, control: impl Into<Control>, value: i32) -> Result<()> {
+        let ctrl_id = control.into().id();
+        let operation = "Camera::set_control_checked";
+        self.control_info(ctrl_id, operation)?
+            .check(value, operation)?;
+        self.set_control(ctrl_id, value)
+    }
+
+    /// The [`ControlInfo`] of a control, or an [`Unsupported`][ErrorKind::Unsupported] error for `operation`.
+    fn control_info(&mut self, ctrl_id: i32, operation: &'static str) -> Result<ControlInfo> {
+        self.supported_controls()?
+            .into_iter()
+            .find(|info| info.id == ctrl_id)
+            .ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::Unsupported,
+                    operation,
+                    format!("control {:#x} is not supported by this camera", ctrl_id),
+                )
+            })
+    }
+
+    /// Set a boolean control, such as [`Control::HFlip`] or [`Control::VFlip`].
+    pub fn set_bool_control(&mut self, control: impl Into<Control>, value: bool) -> Result<()> {
+        self.set_control(control, value as i32)
+    }
+
+    /// Read the current value of a boolean control, such as [`Control::HFlip`] or [`Control::VFlip`].
+    pub fn get_bool_control(&mut self, control: impl Into<Control>) -> Result<bool> {
+        Ok(self.get_control(control)? != 0)
+    }
+
+    /// Set the exposure time, using [`Control::ExposureAbsolute`].
+    ///
+    /// The precision is 100 µs: the time is rounded to the nearest multiple of 100 µs.
+    ///
+    /// Most ArduCAM sensors only support [`Control::Exposure`], in lines, not the exposure time.
+    /// Returns an error of kind [`Unsupported`][ErrorKind::Unsupported] for those,
+    /// or [`InvalidArgument`][ErrorKind::InvalidArgument] if the time is out of range.
+    pub fn set_exposure_time(&mut self, time: Duration) -> Result<()> {
+        let operation = "Camera::set_exposure_time";
+        let info = self.control_info(Control::ExposureAbsolute.id(), operation)?;
+        let units = (time.as_micros() + 50) / 100;
+        let value = i32::try_from(units).map_err(|_| {
+            Error::with_message(
+                ErrorKind::InvalidArgument,
+                operation,
+                format!("exposure time {:?} is too long", time),
+            )
+        })?;
+        info.check(value, operation)?;
+        self.set_control(Control::ExposureAbsolute, value)
+    }
+
+    /// Read the current exposure time, using [`Control::ExposureAbsolute`], with a precision of 100 µs.
+    ///
+    /// Returns an error of kind [`Unsupported`][ErrorKind::Unsupported] if the sensor
+    /// does not support this control, see [`set_exposure_time`][Camera::set_exposure_time].
+    pub fn exposure_time(&mut self) -> Result<Duration> {
+        self.control_info(Control::ExposureAbsolute.id(), "Camera::exposure_time")?;
+        let units = self.get_control(Control::ExposureAbsolute)?;
+        Ok(Duration::from_micros(units.max(0) as u64 * 100))
+    }
+}
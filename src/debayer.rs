@@ -0,0 +1,410 @@
+//! Demosaicing of raw Bayer data to RGB.
+//!
+//! The functions in this module take the raw image without row padding, and
+//! write RGB pixels (three samples per pixel, in row-major order) to the
+//! output. [`to_rgb16`] and [`to_rgb8`] take the data of a raw buffer as
+//! captured by the camera, and unpack it first.
+
+use crate::unpack;
+use crate::{Error, ErrorKind, Format, Result};
+
+/// The order of the color filter array, named after the colors of the top-left 2x2 pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CfaPattern {
+    /// Red, green / green, blue.
+    Rggb,
+    /// Green, red / blue, green.
+    Grbg,
+    /// Green, blue / red, green.
+    Gbrg,
+    /// Blue, green / green, red.
+    Bggr,
+}
+
+impl CfaPattern {
+    /// The color of the pixel at (`x`, `y`): 0 for red, 1 for green, 2 for blue.
+    pub fn channel_at(self, x: usize, y: usize) -> usize {
+        let (red_x, red_y) = match self {
+            CfaPattern::Rggb => (0, 0),
+            CfaPattern::Grbg => (1, 0),
+            CfaPattern::Gbrg => (0, 1),
+            CfaPattern::Bggr => (1, 1),
+        };
+        match (x % 2 == red_x, y % 2 == red_y) {
+            (true, true) => 0,
+            (false, false) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// The demosaicing algorithm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// Bilinear interpolation of the neighbouring pixels of the same color.
+    ///
+    /// Fast, but gives color artifacts at edges.
+    Bilinear,
+    /// Gradient-corrected linear interpolation, as described by Malvar, He and
+    /// Cutler in "High-quality linear interpolation for demosaicing of
+    /// Bayer-patterned color images" (2004).
+    ///
+    /// Somewhat slower, but gives sharper results with fewer artifacts.
+    MalvarHeCutler,
+}
+
+/// Demosaic 8-bit raw data.
+///
+/// `input` must contain `width` times `height` pixels, and `output` three times as many.
+pub fn debayer8(
+    input: &[u8],
+    output: &mut [u8],
+    width: usize,
+    height: usize,
+    cfa: CfaPattern,
+    algorithm: Algorithm,
+) -> Result<()> {
+    check("debayer8", input.len(), output.len(), width, height)?;
+    demosaic(input, output, width, height, cfa, algorithm, |v| {
+        v.clamp(0, 0xFF) as u8
+    });
+    Ok(())
+}
+
+/// Demosaic raw data with `bits` bits per pixel (up to 16) stored in 16-bit samples.
+///
+/// `input` must contain `width` times `height` pixels, and `output` three times as many.
+pub fn debayer16(
+    input: &[u16],
+    output: &mut [u16],
+    width: usize,
+    height: usize,
+    bits: u8,
+    cfa: CfaPattern,
+    algorithm: Algorithm,
+) -> Result<()> {
+    check("debayer16", input.len(), output.len(), width, height)?;
+    if bits == 0 || bits > 16 {
+        return Err(Error::with_message(
+            ErrorKind::InvalidArgument,
+            "debayer16",
+            format!("invalid bit depth: {}", bits),
+        ));
+    }
+    let max = (1 << bits) - 1;
+    demosaic(input, output, width, height, cfa, algorithm, |v| {
+        v.clamp(0, max) as u16
+    });
+    Ok(())
+}
+
+/// Demosaic the data of a raw buffer in the given format, such as the data
+/// of a [`Buffer`][crate::Buffer] captured with
+/// [`Encoding::RawBayer`][crate::Encoding::RawBayer].
+///
+/// Returns the RGB image with 16-bit samples, in the bit depth of the
+/// format. Rows are expected to be padded to a multiple of 32 bytes.
+///
+/// Returns an error of kind [`Unsupported`][ErrorKind::Unsupported] if the
+/// format is not a Bayer format.
+pub fn to_rgb16(data: &[u8], format: &Format, algorithm: Algorithm) -> Result<Vec<u16>> {
+    let width = format.width.max(0) as usize;
+    let height = format.height.max(0) as usize;
+    let pixel_format = format.pixel_format();
+    let (cfa, bits) = match (pixel_format.cfa_pattern(), pixel_format.bit_depth()) {
+        (Some(cfa), Some(bits)) => (cfa, bits),
+        _ => {
+            return Err(Error::with_message(
+                ErrorKind::Unsupported,
+                "debayer::to_rgb16",
+                format!("{:?} is not a Bayer format", pixel_format),
+            ))
+        }
+    };
+    let mut raw = vec![0u16; width * height];
+    unpack::unpack_to_raw16(data, &mut raw, width, height, pixel_format)?;
+    let mut rgb = vec![0u16; width * height * 3];
+    debayer16(&raw, &mut rgb, width, height, bits, cfa, algorithm)?;
+    Ok(rgb)
+}
+
+/// Demosaic the data of a raw buffer in the given format, like [`to_rgb16`],
+/// but scale the result to 8 bits per sample.
+pub fn to_rgb8(data: &[u8], format: &Format, algorithm: Algorithm) -> Result<Vec<u8>> {
+    let bits = format.pixel_format().bit_depth().unwrap_or(8);
+    let rgb = to_rgb16(data, format, algorithm)?;
+    Ok(rgb.into_iter().map(|v| (v >> (bits - 8)) as u8).collect())
+}
+
+fn check(op: &'static str, input: usize, output: usize, width: usize, height: usize) -> Result<()> {
+    if input < width * height || output < width * height * 3 {
+        return Err(Error::with_message(
+            ErrorKind::InvalidArgument,
+            op,
+            format!(
+                "buffers of {} and {} samples are too small for {}x{} pixels",
+                input, output, width, height
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Malvar-He-Cutler kernels, scaled by 16, as (dx, dy, weight).
+///
+/// Green at a red or blue pixel.
+const MHC_GREEN: &[(isize, isize, i32)] = &[
+    (0, 0, 8),
+    (-1, 0, 4),
+    (1, 0, 4),
+    (0, -1, 4),
+    (0, 1, 4),
+    (-2, 0, -2),
+    (2, 0, -2),
+    (0, -2, -2),
+    (0, 2, -2),
+];
+
+/// Red or blue at a green pixel, with that color to the left and right.
+const MHC_HORIZONTAL: &[(isize, isize, i32)] = &[
+    (0, 0, 10),
+    (-1, 0, 8),
+    (1, 0, 8),
+    (-2, 0, -2),
+    (2, 0, -2),
+    (0, -2, 1),
+    (0, 2, 1),
+    (-1, -1, -2),
+    (1, -1, -2),
+    (-1, 1, -2),
+    (1, 1, -2),
+];
+
+/// Red or blue at a green pixel, with that color above and below.
+const MHC_VERTICAL: &[(isize, isize, i32)] = &[
+    (0, 0, 10),
+    (0, -1, 8),
+    (0, 1, 8),
+    (0, -2, -2),
+    (0, 2, -2),
+    (-2, 0, 1),
+    (2, 0, 1),
+    (-1, -1, -2),
+    (1, -1, -2),
+    (-1, 1, -2),
+    (1, 1, -2),
+];
+
+/// Red at a blue pixel, or blue at a red pixel.
+const MHC_DIAGONAL: &[(isize, isize, i32)] = &[
+    (0, 0, 12),
+    (-1, -1, 4),
+    (1, -1, 4),
+    (-1, 1, 4),
+    (1, 1, 4),
+    (-2, 0, -3),
+    (2, 0, -3),
+    (0, -2, -3),
+    (0, 2, -3),
+];
+
+fn demosaic<T>(
+    input: &[T],
+    output: &mut [T],
+    width: usize,
+    height: usize,
+    cfa: CfaPattern,
+    algorithm: Algorithm,
+    to_sample: impl Fn(i32) -> T,
+) where
+    T: Copy + Into<i32>,
+{
+    // Mirror at the edges (repeatedly, for tiny images), which keeps the color
+    // of the mirrored pixel the same, as the period is even.
+    let mirror = |v: isize, size: usize| -> usize {
+        let period = 2 * (size as isize - 1);
+        if period == 0 {
+            return 0;
+        }
+        let v = v.rem_euclid(period);
+        (if v >= size as isize { period - v } else { v }) as usize
+    };
+    let at = |x: usize, y: usize, dx: isize, dy: isize| -> i32 {
+        let x = mirror(x as isize + dx, width);
+        let y = mirror(y as isize + dy, height);
+        input[y * width + x].into()
+    };
+    let convolve = |x: usize, y: usize, kernel: &[(isize, isize, i32)]| -> i32 {
+        let sum: i32 = kernel.iter().map(|&(dx, dy, w)| w * at(x, y, dx, dy)).sum();
+        (sum + 8) >> 4
+    };
+    let mean = |x: usize, y: usize, offsets: &[(isize, isize)]| -> i32 {
+        let sum: i32 = offsets.iter().map(|&(dx, dy)| at(x, y, dx, dy)).sum();
+        (sum + offsets.len() as i32 / 2) / offsets.len() as i32
+    };
+    const CROSS: &[(isize, isize)] = &[(-1, 0), (1, 0), (0, -1), (0, 1)];
+    const DIAGONAL: &[(isize, isize)] = &[(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    const HORIZONTAL: &[(isize, isize)] = &[(-1, 0), (1, 0)];
+    const VERTICAL: &[(isize, isize)] = &[(0, -1), (0, 1)];
+
+    for y in 0..height {
+        for x in 0..width {
+            let channel = cfa.channel_at(x, y);
+            let mut rgb = [0; 3];
+            rgb[channel] = at(x, y, 0, 0);
+            if channel == 1 {
+                // The color to the left and right, and the color above and below.
+                let h = cfa.channel_at(x + 1, y);
+                let v = 2 - h;
+                match algorithm {
+                    Algorithm::Bilinear => {
+                        rgb[h] = mean(x, y, HORIZONTAL);
+                        rgb[v] = mean(x, y, VERTICAL);
+                    }
+                    Algorithm::MalvarHeCutler => {
+                        rgb[h] = convolve(x, y, MHC_HORIZONTAL);
+                        rgb[v] = convolve(x, y, MHC_VERTICAL);
+                    }
+                }
+            } else {
+                let other = 2 - channel;
+                match algorithm {
+                    Algorithm::Bilinear => {
+                        rgb[1] = mean(x, y, CROSS);
+                        rgb[other] = mean(x, y, DIAGONAL);
+                    }
+                    Algorithm::MalvarHeCutler => {
+                        rgb[1] = convolve(x, y, MHC_GREEN);
+                        rgb[other] = convolve(x, y, MHC_DIAGONAL);
+                    }
+                }
+            }
+            let out = &mut output[(y * width + x) * 3..][..3];
+            for (o, &v) in out.iter_mut().zip(&rgb) {
+                *o = to_sample(v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERNS: [CfaPattern; 4] = [
+        CfaPattern::Rggb,
+        CfaPattern::Grbg,
+        CfaPattern::Gbrg,
+        CfaPattern::Bggr,
+    ];
+    const ALGORITHMS: [Algorithm; 2] = [Algorithm::Bilinear, Algorithm::MalvarHeCutler];
+
+    /// A raw image of a scene with a uniform color.
+    fn mosaic(cfa: CfaPattern, width: usize, height: usize, rgb: [u16; 3]) -> Vec<u16> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| rgb[cfa.channel_at(x, y)]))
+            .collect()
+    }
+
+    #[test]
+    fn channel_at() {
+        let top_left = |cfa: CfaPattern| {
+            [
+                cfa.channel_at(0, 0),
+                cfa.channel_at(1, 0),
+                cfa.channel_at(0, 1),
+                cfa.channel_at(1, 1),
+            ]
+        };
+        assert_eq!(top_left(CfaPattern::Rggb), [0, 1, 1, 2]);
+        assert_eq!(top_left(CfaPattern::Grbg), [1, 0, 2, 1]);
+        assert_eq!(top_left(CfaPattern::Gbrg), [1, 2, 0, 1]);
+        assert_eq!(top_left(CfaPattern::Bggr), [2, 1, 1, 0]);
+        for cfa in PATTERNS {
+            for (x, y) in [(2, 0), (0, 2), (4, 6), (7, 3)] {
+                assert_eq!(cfa.channel_at(x, y), cfa.channel_at(x % 2, y % 2));
+            }
+        }
+    }
+
+    #[test]
+    fn flat_field() {
+        let (width, height) = (7, 6);
+        for cfa in PATTERNS {
+            for algorithm in ALGORITHMS {
+                let input = vec![100u8; width * height];
+                let mut output = vec![0; width * height * 3];
+                debayer8(&input, &mut output, width, height, cfa, algorithm).unwrap();
+                assert!(
+                    output.iter().all(|&v| v == 100),
+                    "{:?} {:?}",
+                    cfa,
+                    algorithm
+                );
+
+                let input = vec![700u16; width * height];
+                let mut output = vec![0; width * height * 3];
+                debayer16(&input, &mut output, width, height, 10, cfa, algorithm).unwrap();
+                assert!(
+                    output.iter().all(|&v| v == 700),
+                    "{:?} {:?}",
+                    cfa,
+                    algorithm
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_color() {
+        let (width, height) = (8, 6);
+        let rgb = [100, 500, 900];
+        for cfa in PATTERNS {
+            for algorithm in ALGORITHMS {
+                let input = mosaic(cfa, width, height, rgb);
+                let mut output = vec![0; width * height * 3];
+                debayer16(&input, &mut output, width, height, 10, cfa, algorithm).unwrap();
+                for pixel in output.chunks_exact(3) {
+                    assert_eq!(pixel, rgb, "{:?} {:?}", cfa, algorithm);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiny_images() {
+        for cfa in PATTERNS {
+            for algorithm in ALGORITHMS {
+                // A single pixel has only one color, which is used for all three.
+                let input = [40u8];
+                let mut output = [0; 3];
+                debayer8(&input, &mut output, 1, 1, cfa, algorithm).unwrap();
+                assert_eq!(output, [40; 3], "{:?} {:?}", cfa, algorithm);
+
+                // A 2x2 image has every color.
+                let rgb = [10, 20, 30];
+                let input = mosaic(cfa, 2, 2, rgb);
+                let mut output = [0; 12];
+                debayer16(&input, &mut output, 2, 2, 8, cfa, algorithm).unwrap();
+                for pixel in output.chunks_exact(3) {
+                    assert_eq!(pixel, rgb, "{:?} {:?}", cfa, algorithm);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let cfa = CfaPattern::Rggb;
+        let algorithm = Algorithm::Bilinear;
+        let mut output = [0u8; 11];
+        let r = debayer8(&[0; 4], &mut output, 2, 2, cfa, algorithm);
+        assert_eq!(r.unwrap_err().kind(), ErrorKind::InvalidArgument);
+        let mut output = [0u8; 12];
+        let r = debayer8(&[0; 3], &mut output, 2, 2, cfa, algorithm);
+        assert_eq!(r.unwrap_err().kind(), ErrorKind::InvalidArgument);
+        let mut output = [0u16; 12];
+        let r = debayer16(&[0; 4], &mut output, 2, 2, 17, cfa, algorithm);
+        assert_eq!(r.unwrap_err().kind(), ErrorKind::InvalidArgument);
+    }
+}
@@ -0,0 +1,154 @@
+//! Loading the ArduCAM library at runtime, with the `dlopen` feature.
+//!
+//! The library is loaded by [`Camera::init`][crate::Camera::init] (or earlier
+//! by [`load_library`]), and stays loaded for the rest of the program.
+//! The functions in [`c`][crate::c] call into the loaded library.
+//!
+//! Those functions can't report that the library is missing, so they load the
+//! default library if needed, and panic if that fails. This also applies to the
+//! helpers that don't take a camera, such as
+//! [`arducam_unpack_raw10_to_raw8`][crate::c::arducam_unpack_raw10_to_raw8].
+//! Call [`load_library`] (or [`Camera::init`][crate::Camera::init]) first to
+//! handle the error instead, for example on a host without the library.
+
+use crate::c::{
+    Buffer, CameraCtrl, CameraInstance, CameraInterface, Format, ImageFormat, OutputCallback,
+    PreviewParams, VideoEncoderState,
+};
+use crate::{Error, ErrorKind, Result};
+use libloading::Library;
+use std::ffi::OsStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::OnceLock;
+
+/// The file name of the library, as searched for by `dlopen`.
+pub const DEFAULT_LIBRARY: &str = "libarducam_mipicamera.so";
+
+struct Loaded {
+    functions: Functions,
+    _library: Library,
+}
+
+static LOADED: OnceLock<Loaded> = OnceLock::new();
+
+/// Load the ArduCAM library from the given path.
+///
+/// Without calling this, [`Camera::init`][crate::Camera::init] loads
+/// [`DEFAULT_LIBRARY`] from the default search path of `dlopen`.
+/// Does nothing if a library was already loaded.
+///
+/// Call this before using the functions in [`c`][crate::c] directly without a
+/// [`Camera`][crate::Camera]: they panic if the library can't be loaded.
+///
+/// Returns an error of kind [`LibraryNotFound`][ErrorKind::LibraryNotFound]
+/// if the library could not be loaded, or [`SymbolMissing`][ErrorKind::SymbolMissing]
+/// if it does not contain one of the `arducam_*` functions.
+pub fn load_library(path: impl AsRef<OsStr>) -> Result<()> {
+    load(path.as_ref()).map(|_| ())
+}
+
+pub(crate) fn load_default() -> Result<()> {
+    load_library(DEFAULT_LIBRARY)
+}
+
+fn load(path: &OsStr) -> Result<&'static Loaded> {
+    if let Some(loaded) = LOADED.get() {
+        return Ok(loaded);
+    }
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| Error::with_message(ErrorKind::LibraryNotFound, "dlopen", e.to_string()))?;
+    let functions = unsafe { Functions::load(&library)? };
+    // If another thread got here first, ours is dropped and theirs is used.
+    Ok(LOADED.get_or_init(|| Loaded {
+        functions,
+        _library: library,
+    }))
+}
+
+fn functions() -> &'static Functions {
+    match load(DEFAULT_LIBRARY.as_ref()) {
+        Ok(loaded) => &loaded.functions,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+macro_rules! functions {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*) => {
+        struct Functions {
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        impl Functions {
+            unsafe fn load(library: &Library) -> Result<Self> {
+                Ok(Self {
+                    $($name: *library
+                        .get::<unsafe extern "C" fn($($ty),*) $(-> $ret)?>(
+                            concat!(stringify!($name), "\0").as_bytes(),
+                        )
+                        .map_err(|e| {
+                            Error::with_message(
+                                ErrorKind::SymbolMissing,
+                                stringify!($name),
+                                e.to_string(),
+                            )
+                        })?,)*
+                })
+            }
+        }
+
+        $(
+            #[doc = concat!("Calls `", stringify!($name), "` in the loaded library.")]
+            ///
+            /// # Panics
+            ///
+            /// Panics if no library was loaded and loading the default library fails.
+            ///
+            /// # Safety
+            ///
+            /// The requirements of the C function apply.
+            pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                (functions().$name)($($arg),*)
+            }
+        )*
+    };
+}
+
+functions! {
+    fn arducam_init_camera(camera_instance: *mut CameraInstance) -> c_int;
+    fn arducam_init_camera2(camera_instance: *mut CameraInstance, cam_interface: CameraInterface) -> c_int;
+    fn arducam_set_resolution(camera_instance: CameraInstance, width: *mut c_int, height: *mut c_int) -> c_int;
+    fn arducam_set_mode(camera_instance: CameraInstance, mode: c_int) -> c_int;
+    fn arducam_set_lens_table(camera_instance: CameraInstance, lens_table_path: *mut c_char) -> c_int;
+    fn arducam_get_format(camera_instance: CameraInstance, fmt: *mut Format) -> c_int;
+    fn arducam_set_video_callback(
+        camera_instance: CameraInstance,
+        encoder_state: *mut VideoEncoderState,
+        callback: Option<OutputCallback>,
+        userdata: *mut c_void
+    ) -> c_int;
+    fn arducam_set_raw_callback(camera_instance: CameraInstance, callback: Option<OutputCallback>, userdata: *mut c_void) -> c_int;
+    fn arducam_set_yuv_callback(camera_instance: CameraInstance, callback: Option<OutputCallback>, userdata: *mut c_void) -> c_int;
+    fn arducam_capture(camera_instance: CameraInstance, format: *mut ImageFormat, timeout: c_int) -> *mut Buffer;
+    fn arducam_release_buffer(buffer: *mut Buffer);
+    fn arducam_start_preview(camera_instance: CameraInstance, preview_params: *mut PreviewParams) -> c_int;
+    fn arducam_start_preview_fix_lens(
+        camera_instance: CameraInstance,
+        preview_params: *mut PreviewParams,
+        lens_table_path: *mut c_char
+    ) -> c_int;
+    fn arducam_stop_preview(camera_instance: CameraInstance) -> c_int;
+    fn arducam_close_camera(camera_instance: CameraInstance) -> c_int;
+    fn arducam_reset_control(camera_instance: CameraInstance, ctrl_id: c_int) -> c_int;
+    fn arducam_set_control(camera_instance: CameraInstance, ctrl_id: c_int, value: c_int) -> c_int;
+    fn arducam_get_control(camera_instance: CameraInstance, ctrl_id: c_int, value: *mut c_int) -> c_int;
+    fn arducam_get_gain(camera_instance: CameraInstance, rgain: *mut c_int, bgain: *mut c_int) -> c_int;
+    fn arducam_get_support_formats(camera_instance: CameraInstance, fmt: *mut Format, index: c_int) -> c_int;
+    fn arducam_get_support_controls(camera_instance: CameraInstance, cam_ctrl: *mut CameraCtrl, index: c_int) -> c_int;
+    fn arducam_write_sensor_reg(camera_instance: CameraInstance, address: u16, value: u16) -> c_int;
+    fn arducam_read_sensor_reg(camera_instance: CameraInstance, address: u16, value: *mut u16) -> c_int;
+    fn arducam_software_auto_exposure(camera_instance: CameraInstance, enable: c_int) -> c_int;
+    fn arducam_software_auto_white_balance(camera_instance: CameraInstance, enable: c_int) -> c_int;
+    fn arducam_unpack_raw10_to_raw8(buff_in: *mut u8, width: c_int, height: c_int) -> *mut Buffer;
+    fn arducam_unpack_raw10_to_raw16(buff_in: *mut u8, width: c_int, height: c_int) -> *mut Buffer;
+    fn arducam_manual_set_awb_compensation(r_gain: u32, b_gain: u32);
+}
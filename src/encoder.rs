@@ -0,0 +1,414 @@
+//! Settings for the video encoder.
+
+use crate::{c, Error, ErrorKind, Result};
+
+/// Maximum bitrate for H.264 level 4 with the Baseline and Main profiles, in bits per second.
+///
+/// The limits are MaxBR from table A-1 of the H.264 specification, times the
+/// cpbBrVclFactor of 1000 for Baseline and Main, or 1250 for High (table A-2).
+const MAX_BITRATE_LEVEL4: u32 = 20_000_000;
+/// Maximum bitrate for H.264 level 4 with the High profile, in bits per second.
+const MAX_BITRATE_LEVEL4_HIGH: u32 = 25_000_000;
+/// Maximum bitrate for H.264 level 4.1 and 4.2 with the Baseline and Main profiles, in bits per second.
+const MAX_BITRATE_LEVEL42: u32 = 50_000_000;
+/// Maximum bitrate for H.264 level 4.1 and 4.2 with the High profile, in bits per second.
+const MAX_BITRATE_LEVEL42_HIGH: u32 = 62_500_000;
+/// Maximum quantisation parameter for H.264.
+const MAX_QUANTISATION_PARAMETER: u32 = 51;
+
+/// Video codec.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum VideoCodec {
+    H264 = c::VIDEO_ENCODING_H264,
+    Mjpeg = c::VIDEO_ENCODING_MJPEG,
+}
+
+/// H.264 profile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum H264Profile {
+    Baseline = c::VIDEO_PROFILE_H264_BASELINE,
+    Main = c::VIDEO_PROFILE_H264_MAIN,
+    High = c::VIDEO_PROFILE_H264_HIGH,
+}
+
+/// H.264 level.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum H264Level {
+    Level4 = c::VIDEO_LEVEL_H264_4,
+    Level41 = c::VIDEO_LEVEL_H264_41,
+    Level42 = c::VIDEO_LEVEL_H264_42,
+}
+
+/// H.264 intra refresh mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum IntraRefresh {
+    Cyclic = c::VIDEO_INTRA_REFRESH_CYCLIC,
+    Adaptive = c::VIDEO_INTRA_REFRESH_ADAPTIVE,
+    Both = c::VIDEO_INTRA_REFRESH_BOTH,
+    CyclicMrows = c::VIDEO_INTRA_REFRESH_CYCLIC_MROWS,
+}
+
+/// How the encoder controls the size of the output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RateControl {
+    /// Constant bitrate, in bits per second.
+    Bitrate(u32),
+    /// Variable bitrate with a fixed quantisation parameter.
+    ///
+    /// Lower is better quality. At most 51.
+    QuantisationParameter(u32),
+}
+
+/// Settings for the video encoder, used by [`Camera::start_video`][crate::Camera::start_video].
+///
+/// Use [`VideoEncoderConfig::builder`] to create one.
+/// The default is the same as the default of the C library:
+/// H.264 high profile, level 4, at 17 Mbit/s.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VideoEncoderConfig {
+    codec: VideoCodec,
+    rate_control: RateControl,
+    intra_period: Option<u32>,
+    inline_headers: bool,
+    profile: H264Profile,
+    level: H264Level,
+    inline_motion_vectors: bool,
+    intra_refresh: Option<IntraRefresh>,
+    sps_timing: bool,
+    slices: u32,
+}
+
+/// Builder for a [`VideoEncoderConfig`].
+#[derive(Debug, Clone)]
+pub struct VideoEncoderConfigBuilder {
+    config: VideoEncoderConfig,
+}
+
+impl Default for VideoEncoderConfig {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::H264,
+            rate_control: RateControl::Bitrate(17_000_000),
+            intra_period: None,
+            inline_headers: false,
+            profile: H264Profile::High,
+            level: H264Level::Level4,
+            inline_motion_vectors: false,
+            intra_refresh: None,
+            sps_timing: false,
+            slices: 1,
+        }
+    }
+}
+
+impl VideoEncoderConfig {
+    /// Start building a config, starting from the defaults.
+    pub fn builder() -> VideoEncoderConfigBuilder {
+        VideoEncoderConfigBuilder {
+            config: Self::default(),
+        }
+    }
+
+    /// The video codec.
+    pub fn codec(&self) -> VideoCodec {
+        self.codec
+    }
+
+    /// The rate control mode.
+    pub fn rate_control(&self) -> RateControl {
+        self.rate_control
+    }
+
+    /// The number of frames between keyframes, or `None` for the encoder default.
+    pub fn intra_period(&self) -> Option<u32> {
+        self.intra_period
+    }
+
+    /// Whether SPS and PPS headers are inserted inline in the stream.
+    pub fn inline_headers(&self) -> bool {
+        self.inline_headers
+    }
+
+    /// The H.264 profile.
+    pub fn profile(&self) -> H264Profile {
+        self.profile
+    }
+
+    /// The H.264 level.
+    pub fn level(&self) -> H264Level {
+        self.level
+    }
+
+    /// Whether the encoder outputs inline motion vectors.
+    pub fn inline_motion_vectors(&self) -> bool {
+        self.inline_motion_vectors
+    }
+
+    /// The H.264 intra refresh mode, or `None` to not set it.
+    pub fn intra_refresh(&self) -> Option<IntraRefresh> {
+        self.intra_refresh
+    }
+
+    /// Whether SPS timing information is added to the stream.
+    pub fn sps_timing(&self) -> bool {
+        self.sps_timing
+    }
+
+    /// The number of horizontal slices per frame.
+    pub fn slices(&self) -> u32 {
+        self.slices
+    }
+
+    fn validate(&self) -> Result<()> {
+        let error = |message: String| {
+            Err(Error::with_message(
+                ErrorKind::InvalidArgument,
+                "VideoEncoderConfig",
+                message,
+            ))
+        };
+        match self.rate_control {
+            RateControl::Bitrate(0) => {
+                return error("bitrate must be non-zero".into());
+            }
+            RateControl::Bitrate(bitrate) => {
+                let max = match (self.codec, self.profile, self.level) {
+                    // MJPEG has no levels, the bitrate only has to fit the C interface.
+                    (VideoCodec::Mjpeg, _, _) => i32::MAX as u32,
+                    (VideoCodec::H264, H264Profile::High, H264Level::Level4) => {
+                        MAX_BITRATE_LEVEL4_HIGH
+                    }
+                    (VideoCodec::H264, _, H264Level::Level4) => MAX_BITRATE_LEVEL4,
+                    (VideoCodec::H264, H264Profile::High, _) => MAX_BITRATE_LEVEL42_HIGH,
+                    (VideoCodec::H264, _, _) => MAX_BITRATE_LEVEL42,
+                };
+                if bitrate > max {
+                    return error(format!(
+                        "bitrate {} exceeds the maximum of {}",
+                        bitrate, max
+                    ));
+                }
+            }
+            RateControl::QuantisationParameter(qp) => {
+                if qp > MAX_QUANTISATION_PARAMETER {
+                    return error(format!(
+                        "quantisation parameter {} exceeds the maximum of {}",
+                        qp, MAX_QUANTISATION_PARAMETER
+                    ));
+                }
+            }
+        }
+        if self.codec == VideoCodec::Mjpeg {
+            if self.inline_headers {
+                return error("inline headers are only supported for H.264".into());
+            }
+            if self.inline_motion_vectors {
+                return error("inline motion vectors are only supported for H.264".into());
+            }
+            if self.intra_refresh.is_some() {
+                return error("intra refresh is only supported for H.264".into());
+            }
+            if self.sps_timing {
+                return error("SPS timing is only supported for H.264".into());
+            }
+        }
+        if self.intra_period.is_some_and(|p| p > i32::MAX as u32) {
+            return error("intra period out of range".into());
+        }
+        if self.slices == 0 || self.slices > i32::MAX as u32 {
+            return error(format!("invalid number of slices: {}", self.slices));
+        }
+        Ok(())
+    }
+}
+
+impl VideoEncoderConfigBuilder {
+    /// Set the video codec. Default: [`VideoCodec::H264`].
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.config.codec = codec;
+        self
+    }
+
+    /// Set the rate control mode. Default: 17 Mbit/s.
+    pub fn rate_control(mut self, rate_control: RateControl) -> Self {
+        self.config.rate_control = rate_control;
+        self
+    }
+
+    /// Set the number of frames between keyframes. Default: `None` (encoder default).
+    pub fn intra_period(mut self, frames: Option<u32>) -> Self {
+        self.config.intra_period = frames;
+        self
+    }
+
+    /// Insert SPS and PPS headers inline in the stream. Default: `false`.
+    pub fn inline_headers(mut self, enable: bool) -> Self {
+        self.config.inline_headers = enable;
+        self
+    }
+
+    /// Set the H.264 profile. Default: [`H264Profile::High`].
+    pub fn profile(mut self, profile: H264Profile) -> Self {
+        self.config.profile = profile;
+        self
+    }
+
+    /// Set the H.264 level. Default: [`H264Level::Level4`].
+    pub fn level(mut self, level: H264Level) -> Self {
+        self.config.level = level;
+        self
+    }
+
+    /// Output inline motion vectors. Default: `false`.
+    pub fn inline_motion_vectors(mut self, enable: bool) -> Self {
+        self.config.inline_motion_vectors = enable;
+        self
+    }
+
+    /// Set the H.264 intra refresh mode. Default: `None` (not set).
+    pub fn intra_refresh(mut self, mode: Option<IntraRefresh>) -> Self {
+        self.config.intra_refresh = mode;
+        self
+    }
+
+    /// Add SPS timing information to the stream. Default: `false`.
+    pub fn sps_timing(mut self, enable: bool) -> Self {
+        self.config.sps_timing = enable;
+        self
+    }
+
+    /// Set the number of horizontal slices per frame. Default: 1.
+    pub fn slices(mut self, slices: u32) -> Self {
+        self.config.slices = slices;
+        self
+    }
+
+    /// Check the settings and create the [`VideoEncoderConfig`].
+    ///
+    /// Returns an error of kind [`InvalidArgument`][ErrorKind::InvalidArgument]
+    /// for invalid combinations, such as a bitrate above the limit of the H.264 profile and level.
+    pub fn build(self) -> Result<VideoEncoderConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+impl From<VideoEncoderConfig> for c::VideoEncoderState {
+    fn from(config: VideoEncoderConfig) -> Self {
+        let (bitrate, quantisation_parameter) = match config.rate_control {
+            RateControl::Bitrate(bitrate) => (bitrate, 0),
+            RateControl::QuantisationParameter(qp) => (0, qp),
+        };
+        c::VideoEncoderState {
+            encoding: config.codec as u32,
+            bitrate: bitrate as i32,
+            intraperiod: config.intra_period.map_or(-1, |p| p as i32),
+            quantisation_parameter: quantisation_parameter as i32,
+            b_inline_headers: config.inline_headers as i32,
+            immutable_input: 1,
+            profile: config.profile as i32,
+            level: config.level as i32,
+            inline_motion_vectors: config.inline_motion_vectors as i32,
+            intra_refresh_type: config.intra_refresh.map_or(-1, |r| r as i32),
+            add_sps_timing: config.sps_timing as i32,
+            slices: config.slices as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h264(profile: H264Profile, level: H264Level, bitrate: u32) -> Result<VideoEncoderConfig> {
+        VideoEncoderConfig::builder()
+            .profile(profile)
+            .level(level)
+            .rate_control(RateControl::Bitrate(bitrate))
+            .build()
+    }
+
+    #[test]
+    fn bitrate_limits() {
+        use H264Level::*;
+        use H264Profile::*;
+        for &(profile, level, max) in &[
+            (Baseline, Level4, 20_000_000),
+            (Main, Level4, 20_000_000),
+            (High, Level4, 25_000_000),
+            (Baseline, Level41, 50_000_000),
+            (Main, Level42, 50_000_000),
+            (High, Level41, 62_500_000),
+            (High, Level42, 62_500_000),
+        ] {
+            assert!(
+                h264(profile, level, max).is_ok(),
+                "{:?} {:?}",
+                profile,
+                level
+            );
+            let e = h264(profile, level, max + 1).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+        }
+        assert!(h264(High, Level4, 0).is_err());
+        let mjpeg = VideoEncoderConfig::builder().codec(VideoCodec::Mjpeg);
+        let high = mjpeg
+            .clone()
+            .rate_control(RateControl::Bitrate(100_000_000));
+        assert!(high.build().is_ok());
+        let overflow = mjpeg.clone().rate_control(RateControl::Bitrate(u32::MAX));
+        assert!(overflow.build().is_err());
+    }
+
+    #[test]
+    fn quantisation_parameter() {
+        let qp = |qp| {
+            VideoEncoderConfig::builder()
+                .rate_control(RateControl::QuantisationParameter(qp))
+                .build()
+        };
+        assert!(qp(0).is_ok());
+        assert!(qp(51).is_ok());
+        assert_eq!(qp(52).unwrap_err().kind(), ErrorKind::InvalidArgument);
+    }
+
+    #[test]
+    fn h264_only_options() {
+        let mjpeg = VideoEncoderConfig::builder().codec(VideoCodec::Mjpeg);
+        assert!(mjpeg.clone().build().is_ok());
+        assert!(mjpeg.clone().inline_headers(true).build().is_err());
+        assert!(mjpeg.clone().inline_motion_vectors(true).build().is_err());
+        assert!(mjpeg
+            .clone()
+            .intra_refresh(Some(IntraRefresh::Cyclic))
+            .build()
+            .is_err());
+        assert!(mjpeg.clone().sps_timing(true).build().is_err());
+        let h264 = VideoEncoderConfig::builder()
+            .inline_headers(true)
+            .inline_motion_vectors(true)
+            .intra_refresh(Some(IntraRefresh::Both))
+            .sps_timing(true);
+        assert!(h264.build().is_ok());
+    }
+
+    #[test]
+    fn counts() {
+        let builder = VideoEncoderConfig::builder();
+        assert!(builder.clone().slices(0).build().is_err());
+        assert!(builder.clone().slices(4).build().is_ok());
+        assert!(builder
+            .clone()
+            .intra_period(Some(u32::MAX))
+            .build()
+            .is_err());
+        let config = builder.clone().intra_period(Some(60)).build().unwrap();
+        let state = c::VideoEncoderState::from(config);
+        assert_eq!(state.intraperiod, 60);
+        assert_eq!(state.bitrate, 17_000_000);
+    }
+}
@@ -0,0 +1,191 @@
+//! Error type returned by the [`Camera`][crate::Camera] interface.
+
+use std::fmt;
+
+/// Result type used throughout this crate.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error returned when an operation on the camera fails.
+///
+/// Carries the name of the operation that failed, the return code of the C
+/// library (if there was one), and a rough classification of the error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    operation: &'static str,
+    code: Option<i32>,
+    message: Option<String>,
+}
+
+/// Classification of an [`Error`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The operation did not complete in time.
+    Timeout,
+    /// An argument (e.g. a control id or a resolution) was not accepted.
+    InvalidArgument,
+    /// The operation is not supported by the camera or the library.
+    Unsupported,
+    /// The camera is busy or not ready for this operation.
+    Busy,
+    /// Communication with the sensor (e.g. over I2C) failed.
+    Io,
+    /// The ArduCAM library could not be loaded (with the `dlopen` feature).
+    LibraryNotFound,
+    /// The ArduCAM library is missing a function (with the `dlopen` feature).
+    SymbolMissing,
+    /// Any other error.
+    Other,
+}
+
+impl Error {
+    /// Create a new error for the given operation, without a C return code.
+    pub fn new(kind: ErrorKind, operation: &'static str) -> Self {
+        Self {
+            kind,
+            operation,
+            code: None,
+            message: None,
+        }
+    }
+
+    /// Create a new error for the given operation, with a message describing what went wrong.
+    pub fn with_message(
+        kind: ErrorKind,
+        operation: &'static str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            operation,
+            code: None,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Create an error from a non-zero return code of a C function.
+    ///
+    /// Negative codes are interpreted as negated `errno` values, as returned
+    /// by the V4L2 and I2C parts of the library. Positive codes are
+    /// interpreted as `MMAL_STATUS_T` values, as returned by the MMAL parts.
+    pub fn from_code(code: i32, operation: &'static str) -> Self {
+        Self {
+            kind: ErrorKind::from_code(code),
+            operation,
+            code: Some(code),
+            message: None,
+        }
+    }
+
+    /// The classification of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The operation that failed, usually the name of the C function.
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// The return code of the C function, if any.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// A message with more details about the error, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Add a message to an error that does not have one yet.
+    pub(crate) fn context(mut self, message: impl Into<String>) -> Self {
+        if self.message.is_none() {
+            self.message = Some(message.into());
+        }
+        self
+    }
+}
+
+impl ErrorKind {
+    fn from_code(code: i32) -> Self {
+        if code < 0 {
+            match code.checked_neg() {
+                Some(ETIMEDOUT) | Some(EAGAIN) => ErrorKind::Timeout,
+                Some(EINVAL) | Some(ERANGE) | Some(ENOENT) => ErrorKind::InvalidArgument,
+                Some(ENOTTY) | Some(ENOSYS) | Some(EOPNOTSUPP) => ErrorKind::Unsupported,
+                Some(EBUSY) => ErrorKind::Busy,
+                Some(EIO) | Some(ENXIO) | Some(EREMOTEIO) => ErrorKind::Io,
+                _ => ErrorKind::Other,
+            }
+        } else {
+            match code {
+                MMAL_EAGAIN => ErrorKind::Timeout,
+                MMAL_EINVAL | MMAL_ENOENT | MMAL_ECONFIG => ErrorKind::InvalidArgument,
+                MMAL_ENOSYS => ErrorKind::Unsupported,
+                MMAL_ENOTREADY | MMAL_EISCONN => ErrorKind::Busy,
+                MMAL_ENXIO | MMAL_EIO => ErrorKind::Io,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "timed out",
+            ErrorKind::InvalidArgument => "invalid argument",
+            ErrorKind::Unsupported => "not supported",
+            ErrorKind::Busy => "device busy",
+            ErrorKind::Io => "I/O error",
+            ErrorKind::LibraryNotFound => "library not found",
+            ErrorKind::SymbolMissing => "symbol missing",
+            ErrorKind::Other => "error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed: {}", self.operation, self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if let Some(code) = self.code {
+            write!(f, " (code {})", code)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+// errno values (Linux).
+const ENOENT: i32 = 2;
+const EIO: i32 = 5;
+const ENXIO: i32 = 6;
+const EAGAIN: i32 = 11;
+const EBUSY: i32 = 16;
+const EINVAL: i32 = 22;
+const ENOTTY: i32 = 25;
+const ERANGE: i32 = 34;
+const ENOSYS: i32 = 38;
+const EOPNOTSUPP: i32 = 95;
+const ETIMEDOUT: i32 = 110;
+const EREMOTEIO: i32 = 121;
+
+// MMAL_STATUS_T values.
+const MMAL_EINVAL: i32 = 3;
+const MMAL_ENOSYS: i32 = 4;
+const MMAL_ENOENT: i32 = 5;
+const MMAL_ENXIO: i32 = 6;
+const MMAL_EIO: i32 = 7;
+const MMAL_ENOTREADY: i32 = 10;
+const MMAL_ECONFIG: i32 = 11;
+const MMAL_EISCONN: i32 = 12;
+const MMAL_EAGAIN: i32 = 14;
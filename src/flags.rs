@@ -0,0 +1,70 @@
+//! Buffer metadata flags.
+
+use crate::c;
+
+bitflags::bitflags! {
+    /// The `MMAL_BUFFER_HEADER_FLAG_*` flags of a buffer.
+    ///
+    /// Unknown bits are kept, see [`bits`][BufferFlags::bits] for the raw value.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    pub struct BufferFlags: u32 {
+        /// The payload is the end of the stream of data.
+        const EOS = c::MMAL_BUFFER_HEADER_FLAG_EOS;
+        /// The payload starts a frame.
+        const FRAME_START = c::MMAL_BUFFER_HEADER_FLAG_FRAME_START;
+        /// The payload ends a frame.
+        const FRAME_END = c::MMAL_BUFFER_HEADER_FLAG_FRAME_END;
+        /// The payload contains only complete frames (1 or more).
+        const FRAME = c::MMAL_BUFFER_HEADER_FLAG_FRAME;
+        /// The payload is a keyframe (i.e. self decodable).
+        const KEYFRAME = c::MMAL_BUFFER_HEADER_FLAG_KEYFRAME;
+        /// A discontinuity in the stream of data.
+        const DISCONTINUITY = c::MMAL_BUFFER_HEADER_FLAG_DISCONTINUITY;
+        /// The payload is config data for the codec.
+        const CONFIG = c::MMAL_BUFFER_HEADER_FLAG_CONFIG;
+        /// The payload is encrypted.
+        const ENCRYPTED = c::MMAL_BUFFER_HEADER_FLAG_ENCRYPTED;
+        /// The payload contains side information.
+        const CODEC_SIDE_INFO = c::MMAL_BUFFER_HEADER_FLAG_CODECSIDEINFO;
+        /// The payload is the snapshot/postview image from a stills capture.
+        const SNAPSHOT = c::MMAL_BUFFER_HEADER_FLAGS_SNAPSHOT;
+        /// The payload is known to be corrupted.
+        const CORRUPTED = c::MMAL_BUFFER_HEADER_FLAG_CORRUPTED;
+        /// The buffer failed to be transmitted.
+        const TRANSMISSION_FAILED = c::MMAL_BUFFER_HEADER_FLAG_TRANSMISSION_FAILED;
+        /// The buffer is only used to update reference frames.
+        const DECODE_ONLY = c::MMAL_BUFFER_HEADER_FLAG_DECODEONLY;
+        /// The payload ends a NAL unit.
+        const NAL_END = c::MMAL_BUFFER_HEADER_FLAG_NAL_END;
+
+        // Keep unknown bits.
+        const _ = !0;
+    }
+}
+
+impl BufferFlags {
+    /// The payload is (part of) a keyframe.
+    pub fn is_keyframe(self) -> bool {
+        self.contains(BufferFlags::KEYFRAME)
+    }
+
+    /// The payload contains codec config data (e.g. SPS and PPS headers), not a frame.
+    pub fn is_config(self) -> bool {
+        self.contains(BufferFlags::CONFIG)
+    }
+
+    /// The payload is known to be corrupted, or failed to be transmitted.
+    pub fn is_corrupted(self) -> bool {
+        self.intersects(BufferFlags::CORRUPTED | BufferFlags::TRANSMISSION_FAILED)
+    }
+
+    /// The payload ends a frame.
+    pub fn is_frame_end(self) -> bool {
+        self.contains(BufferFlags::FRAME_END)
+    }
+
+    /// The payload is the end of the stream.
+    pub fn is_eos(self) -> bool {
+        self.contains(BufferFlags::EOS)
+    }
+}
@@ -0,0 +1,252 @@
+//! Sensor modes and pixel formats.
+
+use crate::debayer::CfaPattern;
+use crate::{align_up, c, to_result, Camera, Format, Fract, Result};
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+
+macro_rules! pixel_formats {
+    ($($(#[$doc:meta])* $name:ident = $fourcc:literal => ($cfa:expr, $bits:literal, $packing:ident),)*) => {
+        /// Pixel format, as identified by a V4L2 fourcc code.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum PixelFormat {
+            $($(#[$doc])* $name,)*
+            /// Any other fourcc code.
+            Unknown(u32),
+        }
+
+        impl PixelFormat {
+            /// Decode a fourcc code, such as the `pixelformat` of a [`Format`].
+            pub fn from_fourcc(fourcc: u32) -> Self {
+                $(
+                    if fourcc == u32::from_le_bytes(*$fourcc) {
+                        return PixelFormat::$name;
+                    }
+                )*
+                PixelFormat::Unknown(fourcc)
+            }
+
+            /// The fourcc code of this pixel format.
+            pub fn fourcc(self) -> u32 {
+                match self {
+                    $(PixelFormat::$name => u32::from_le_bytes(*$fourcc),)*
+                    PixelFormat::Unknown(fourcc) => fourcc,
+                }
+            }
+
+            /// The CFA order, bit depth and packing, or `None` for an unknown format.
+            fn layout(self) -> Option<(Option<CfaPattern>, u8, Packing)> {
+                use CfaPattern::*;
+                use Packing::*;
+                match self {
+                    $(PixelFormat::$name => Some(($cfa, $bits, $packing)),)*
+                    PixelFormat::Unknown(_) => None,
+                }
+            }
+        }
+    };
+}
+
+pixel_formats! {
+    /// 8-bit Bayer, BGGR order.
+    Bggr8 = b"BA81" => (Some(Bggr), 8, Unpacked),
+    /// 8-bit Bayer, GBRG order.
+    Gbrg8 = b"GBRG" => (Some(Gbrg), 8, Unpacked),
+    /// 8-bit Bayer, GRBG order.
+    Grbg8 = b"GRBG" => (Some(Grbg), 8, Unpacked),
+    /// 8-bit Bayer, RGGB order.
+    Rggb8 = b"RGGB" => (Some(Rggb), 8, Unpacked),
+    /// 10-bit Bayer, BGGR order, MIPI packed.
+    Bggr10Packed = b"pBAA" => (Some(Bggr), 10, Mipi),
+    /// 10-bit Bayer, GBRG order, MIPI packed.
+    Gbrg10Packed = b"pGAA" => (Some(Gbrg), 10, Mipi),
+    /// 10-bit Bayer, GRBG order, MIPI packed.
+    Grbg10Packed = b"pgAA" => (Some(Grbg), 10, Mipi),
+    /// 10-bit Bayer, RGGB order, MIPI packed.
+    Rggb10Packed = b"pRAA" => (Some(Rggb), 10, Mipi),
+    /// 12-bit Bayer, BGGR order, MIPI packed.
+    Bggr12Packed = b"pBCC" => (Some(Bggr), 12, Mipi),
+    /// 12-bit Bayer, GBRG order, MIPI packed.
+    Gbrg12Packed = b"pGCC" => (Some(Gbrg), 12, Mipi),
+    /// 12-bit Bayer, GRBG order, MIPI packed.
+    Grbg12Packed = b"pgCC" => (Some(Grbg), 12, Mipi),
+    /// 12-bit Bayer, RGGB order, MIPI packed.
+    Rggb12Packed = b"pRCC" => (Some(Rggb), 12, Mipi),
+    /// 10-bit Bayer, BGGR order, in 16 bits per pixel.
+    Bggr10 = b"BG10" => (Some(Bggr), 10, Unpacked),
+    /// 10-bit Bayer, GBRG order, in 16 bits per pixel.
+    Gbrg10 = b"GB10" => (Some(Gbrg), 10, Unpacked),
+    /// 10-bit Bayer, GRBG order, in 16 bits per pixel.
+    Grbg10 = b"BA10" => (Some(Grbg), 10, Unpacked),
+    /// 10-bit Bayer, RGGB order, in 16 bits per pixel.
+    Rggb10 = b"RG10" => (Some(Rggb), 10, Unpacked),
+    /// 12-bit Bayer, BGGR order, in 16 bits per pixel.
+    Bggr12 = b"BG12" => (Some(Bggr), 12, Unpacked),
+    /// 12-bit Bayer, GBRG order, in 16 bits per pixel.
+    Gbrg12 = b"GB12" => (Some(Gbrg), 12, Unpacked),
+    /// 12-bit Bayer, GRBG order, in 16 bits per pixel.
+    Grbg12 = b"BA12" => (Some(Grbg), 12, Unpacked),
+    /// 12-bit Bayer, RGGB order, in 16 bits per pixel.
+    Rggb12 = b"RG12" => (Some(Rggb), 12, Unpacked),
+    /// 16-bit Bayer, BGGR order.
+    Bggr16 = b"BYR2" => (Some(Bggr), 16, Unpacked),
+    /// 16-bit Bayer, GBRG order.
+    Gbrg16 = b"GB16" => (Some(Gbrg), 16, Unpacked),
+    /// 16-bit Bayer, GRBG order.
+    Grbg16 = b"GR16" => (Some(Grbg), 16, Unpacked),
+    /// 16-bit Bayer, RGGB order.
+    Rggb16 = b"RG16" => (Some(Rggb), 16, Unpacked),
+    /// 8-bit monochrome.
+    Grey = b"GREY" => (None, 8, Unpacked),
+    /// 10-bit monochrome, in 16 bits per pixel.
+    Y10 = b"Y10 " => (None, 10, Unpacked),
+    /// 10-bit monochrome, MIPI packed.
+    Y10Packed = b"Y10P" => (None, 10, Mipi),
+    /// 12-bit monochrome, in 16 bits per pixel.
+    Y12 = b"Y12 " => (None, 12, Unpacked),
+    /// 12-bit monochrome, MIPI packed.
+    Y12Packed = b"Y12P" => (None, 12, Mipi),
+    /// 16-bit monochrome.
+    Y16 = b"Y16 " => (None, 16, Unpacked),
+}
+
+/// How the pixels of a raw format are stored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Packing {
+    /// One byte per pixel for 8-bit formats, otherwise two bytes per pixel (little endian).
+    Unpacked,
+    /// MIPI packing: four 10-bit pixels in five bytes, or two 12-bit pixels in
+    /// three bytes. See [`unpack`][crate::unpack].
+    Mipi,
+}
+
+impl PixelFormat {
+    /// The order of the color filter array, or `None` for monochrome or unknown formats.
+    pub fn cfa_pattern(self) -> Option<CfaPattern> {
+        self.layout().and_then(|l| l.0)
+    }
+
+    /// The number of significant bits per pixel, or `None` for unknown formats.
+    pub fn bit_depth(self) -> Option<u8> {
+        self.layout().map(|l| l.1)
+    }
+
+    /// How the pixels are stored, or `None` for unknown formats.
+    pub fn packing(self) -> Option<Packing> {
+        self.layout().map(|l| l.2)
+    }
+
+    /// Whether this is a raw Bayer format.
+    pub fn is_bayer(self) -> bool {
+        self.cfa_pattern().is_some()
+    }
+
+    /// Whether this is a monochrome format.
+    pub fn is_monochrome(self) -> bool {
+        self.layout().is_some_and(|l| l.0.is_none())
+    }
+
+    /// The number of bytes per row of `width` pixels, without padding.
+    ///
+    /// Returns `None` for unknown formats.
+    pub fn row_bytes(self, width: usize) -> Option<usize> {
+        let (_, bits, packing) = self.layout()?;
+        Some(match (packing, bits) {
+            (Packing::Unpacked, 8) => width,
+            (Packing::Unpacked, _) => width * 2,
+            (Packing::Mipi, bits) => (width * usize::from(bits)).div_ceil(8),
+        })
+    }
+
+    /// The number of bytes between the start of two rows of `width` pixels in
+    /// the buffers of the camera, which pads the rows to a multiple of 32 bytes.
+    ///
+    /// Returns `None` for unknown formats.
+    pub fn stride(self, width: usize) -> Option<usize> {
+        Some(align_up(self.row_bytes(width)?, 32))
+    }
+}
+
+impl Format {
+    /// The decoded [`pixelformat`][Format::pixelformat].
+    pub fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::from_fourcc(self.pixelformat)
+    }
+}
+
+/// A mode supported by the sensor, as returned by [`Camera::supported_formats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensorMode {
+    /// The mode index, as used by [`Camera::set_mode`].
+    pub mode: i32,
+    /// Width in pixels.
+    pub width: i32,
+    /// Height in pixels.
+    pub height: i32,
+    /// The pixel format of the raw sensor data.
+    pub pixel_format: PixelFormat,
+    /// The time between frames, as a fraction of seconds.
+    pub frame_interval: Fract,
+    /// Human readable description of the mode.
+    pub description: String,
+}
+
+impl SensorMode {
+    /// The (maximum) frame rate in frames per second.
+    ///
+    /// Returns `None` if the frame interval is unknown.
+    pub fn frame_rate(&self) -> Option<f64> {
+        let Fract {
+            numerator,
+            denominator,
+        } = self.frame_interval;
+        if numerator == 0 {
+            None
+        } else {
+            Some(f64::from(denominator) / f64::from(numerator))
+        }
+    }
+
+    /// Convert a [`Format`] returned by the library.
+    ///
+    /// # Safety
+    ///
+    /// `format.description` must be null or point to a valid nul-terminated string.
+    pub(crate) unsafe fn from_raw(format: &Format) -> Self {
+        let description = if format.description.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(format.description)
+                .to_string_lossy()
+                .into_owned()
+        };
+        Self {
+            mode: format.mode,
+            width: format.width,
+            height: format.height,
+            pixel_format: PixelFormat::from_fourcc(format.pixelformat),
+            frame_interval: format.frameintervals,
+            description,
+        }
+    }
+}
+
+impl Camera {
+    /// Get all modes supported by the sensor.
+    pub fn supported_formats(&mut self) -> Result<Vec<SensorMode>> {
+        let mut modes = Vec::new();
+        for index in 0.. {
+            let mut format = MaybeUninit::uninit();
+            let r = unsafe { c::arducam_get_support_formats(self.ptr, format.as_mut_ptr(), index) };
+            if r != 0 {
+                if index == 0 {
+                    to_result(r, "arducam_get_support_formats")?;
+                }
+                break;
+            }
+            // The library sets the description to a static string.
+            modes.push(unsafe { SensorMode::from_raw(&format.assume_init()) });
+        }
+        Ok(modes)
+    }
+}
@@ -0,0 +1,236 @@
+//! Frames with their format and memory layout.
+
+use crate::{
+    align_up, c, Buffer, BufferFlags, Camera, Encoding, Format, OutputKind, PixelFormat, Result,
+};
+
+/// Where a [`Frame`] came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameSource {
+    /// A still image from [`Camera::capture`], in the requested encoding.
+    Capture(Encoding),
+    /// A camera output, such as [`Camera::frames`][crate::Camera::frames].
+    Output(OutputKind),
+}
+
+/// How the pixels of a frame are stored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelLayout {
+    /// Raw sensor data, in the given format.
+    Raw(PixelFormat),
+    /// YUV 4:2:0, with a full resolution Y plane, followed by a U and a V plane at half resolution.
+    I420,
+    /// Encoded data (JPEG, PNG, BMP, H.264 or MJPEG), without a pixel layout.
+    Encoded,
+}
+
+/// The dimensions and memory layout of a frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameLayout {
+    /// How the pixels are stored.
+    pub pixels: PixelLayout,
+    /// Width of the image, in pixels.
+    pub width: usize,
+    /// Height of the image, in pixels.
+    pub height: usize,
+    /// Number of bytes between the start of two rows (of the Y plane, for I420).
+    ///
+    /// Zero for encoded data.
+    pub stride: usize,
+    /// Number of rows (of the Y plane, for I420) including the padding at the bottom.
+    pub padded_height: usize,
+}
+
+impl FrameLayout {
+    /// The layout of a frame of the given format.
+    ///
+    /// Rows are padded to a multiple of 32 bytes, and the height to a multiple of 16 rows.
+    pub fn new(pixels: PixelLayout, width: usize, height: usize) -> Self {
+        let (stride, padded_height) = match pixels {
+            PixelLayout::Raw(format) => (format.stride(width).unwrap_or(0), align_up(height, 16)),
+            PixelLayout::I420 => (align_up(width, 32), align_up(height, 16)),
+            PixelLayout::Encoded => (0, height),
+        };
+        Self {
+            pixels,
+            width,
+            height,
+            stride,
+            padded_height,
+        }
+    }
+
+    /// The number of bytes of a row (of the Y plane, for I420), without padding.
+    ///
+    /// Zero for encoded data or an unknown raw format.
+    pub fn row_bytes(&self) -> usize {
+        match self.pixels {
+            PixelLayout::Raw(format) => format.row_bytes(self.width).unwrap_or(0),
+            PixelLayout::I420 => self.width,
+            PixelLayout::Encoded => 0,
+        }
+    }
+
+    fn for_source(source: FrameSource, format: &Format) -> Self {
+        let pixels = match source {
+            FrameSource::Capture(Encoding::I420) | FrameSource::Output(OutputKind::Yuv) => {
+                PixelLayout::I420
+            }
+            FrameSource::Capture(Encoding::RawBayer) | FrameSource::Output(OutputKind::Raw) => {
+                PixelLayout::Raw(format.pixel_format())
+            }
+            _ => PixelLayout::Encoded,
+        };
+        Self::new(
+            pixels,
+            format.width.max(0) as usize,
+            format.height.max(0) as usize,
+        )
+    }
+}
+
+/// A frame: image data along with where it came from, its format and its layout.
+///
+/// Returned by [`Camera::capture`] with the data in a [`Buffer`], and by
+/// [`Frames`][crate::Frames] with the data copied into a `Vec<u8>`.
+#[derive(Debug, Clone)]
+pub struct Frame<D = Vec<u8>> {
+    source: FrameSource,
+    data: D,
+    timestamp: Option<i64>,
+    flags: BufferFlags,
+    format: Format,
+    layout: FrameLayout,
+}
+
+impl Frame {
+    pub(crate) fn from_raw_buffer(kind: OutputKind, buffer: &c::Buffer, format: Format) -> Self {
+        let data = unsafe { buffer.bytes() }.to_vec();
+        let source = FrameSource::Output(kind);
+        Self::new(source, data, buffer.timestamp(), buffer.flags, format)
+    }
+}
+
+impl<'cam> Frame<Buffer<'cam>> {
+    /// The buffer holding the data.
+    pub fn buffer(&self) -> &Buffer<'cam> {
+        &self.data
+    }
+}
+
+impl<D: AsRef<[u8]>> Frame<D> {
+    fn new(
+        source: FrameSource,
+        data: D,
+        timestamp: Option<i64>,
+        flags: u32,
+        format: Format,
+    ) -> Self {
+        Self {
+            source,
+            data,
+            timestamp,
+            flags: BufferFlags::from_bits_retain(flags),
+            layout: FrameLayout::for_source(source, &format),
+            format,
+        }
+    }
+
+    /// Where this frame came from.
+    pub fn source(&self) -> FrameSource {
+        self.source
+    }
+
+    /// The output this frame came from, or `None` for a frame from [`Camera::capture`].
+    pub fn kind(&self) -> Option<OutputKind> {
+        match self.source {
+            FrameSource::Output(kind) => Some(kind),
+            FrameSource::Capture(_) => None,
+        }
+    }
+
+    /// The data of the frame, including padding.
+    pub fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /// Take the data out of the frame.
+    pub fn into_data(self) -> D {
+        self.data
+    }
+
+    /// The presentation timestamp of the frame.
+    ///
+    /// Returns `None` when the timestamp was set to `TIME_UNKNOWN`.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    /// The metadata flags of the frame.
+    pub fn flags(&self) -> BufferFlags {
+        self.flags
+    }
+
+    /// The format of the camera at the time the frame was captured or the output was started.
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    /// The dimensions and memory layout of the frame.
+    pub fn layout(&self) -> &FrameLayout {
+        &self.layout
+    }
+
+    /// Width of the image, in pixels.
+    pub fn width(&self) -> usize {
+        self.layout.width
+    }
+
+    /// Height of the image, in pixels.
+    pub fn height(&self) -> usize {
+        self.layout.height
+    }
+
+    /// Number of bytes between the start of two rows, see [`FrameLayout::stride`].
+    pub fn stride(&self) -> usize {
+        self.layout.stride
+    }
+
+    /// A row of the image (of the Y plane, for I420), without padding.
+    ///
+    /// Returns `None` if the row is out of range, for encoded data, or if the buffer is too short.
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        let row_bytes = self.layout.row_bytes();
+        if y >= self.layout.height || row_bytes == 0 {
+            return None;
+        }
+        self.data().get(y * self.layout.stride..)?.get(..row_bytes)
+    }
+
+    /// Iterate over the rows of the image (of the Y plane, for I420), without padding.
+    ///
+    /// Yields nothing for encoded data.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.layout.height).map_while(move |y| self.row(y))
+    }
+}
+
+impl Camera {
+    /// Capture a single frame.
+    ///
+    /// The frame is tagged with the current [`Format`] of the camera.
+    /// Returns an error of kind [`Other`][crate::ErrorKind::Other] if the library returned no frame,
+    /// see [`Camera::capture_buffer`].
+    pub fn capture(
+        &self,
+        timeout: i32,
+        encoding: Encoding,
+        quality: i32,
+    ) -> Result<Frame<Buffer<'_>>> {
+        let format = self.get_format()?;
+        let buffer = self.capture_buffer(timeout, encoding, quality)?;
+        let (timestamp, flags) = (buffer.timestamp(), buffer.raw_buffer().flags);
+        let source = FrameSource::Capture(encoding);
+        Ok(Frame::new(source, buffer, timestamp, flags, format))
+    }
+}
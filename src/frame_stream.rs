@@ -0,0 +1,125 @@
+//! Frame delivery as an asynchronous [`Stream`].
+
+use crate::frames::Queue;
+use crate::{Frame, Frames, Result, SharedFrames};
+use futures_core::Stream;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Asynchronous stream of the frames of an output, returned by [`Frames::into_stream`].
+///
+/// Stops the output when dropped.
+/// Dereferences to the [`Frames`], to allow setting the overflow policy or
+/// changing camera controls while the output is running.
+///
+/// This borrows the camera. Use [`SharedFrameStream`] for a stream that can be
+/// moved to another task, such as with `tokio::spawn`.
+pub struct FrameStream<'a> {
+    frames: Frames<'a>,
+}
+
+/// Asynchronous stream of the frames of an output, returned by [`SharedFrames::into_stream`].
+///
+/// Same as [`FrameStream`], except that it owns a handle to the camera,
+/// so it is `'static` and [`Send`].
+/// Dereferences to the [`SharedFrames`].
+pub struct SharedFrameStream {
+    frames: SharedFrames,
+}
+
+impl<'a> Frames<'a> {
+    /// Turn the blocking iterator into an asynchronous [`Stream`].
+    ///
+    /// The timeout set by [`Frames::set_timeout`] does not apply to the stream.
+    pub fn into_stream(self) -> FrameStream<'a> {
+        FrameStream { frames: self }
+    }
+}
+
+impl SharedFrames {
+    /// Turn the blocking iterator into an asynchronous [`Stream`].
+    ///
+    /// The timeout set by [`SharedFrames::set_timeout`] does not apply to the stream.
+    pub fn into_stream(self) -> SharedFrameStream {
+        SharedFrameStream { frames: self }
+    }
+}
+
+impl FrameStream<'_> {
+    /// Stop the output.
+    ///
+    /// Same as dropping the stream, but reports errors.
+    pub fn stop(self) -> Result<()> {
+        self.frames.stop()
+    }
+}
+
+impl SharedFrameStream {
+    /// Stop the output.
+    ///
+    /// Same as dropping the stream, but reports errors.
+    pub fn stop(self) -> Result<()> {
+        self.frames.stop()
+    }
+}
+
+impl Stream for FrameStream<'_> {
+    type Item = Result<Frame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Result<Frame>>> {
+        self.frames.queue.poll_next(cx)
+    }
+}
+
+impl Stream for SharedFrameStream {
+    type Item = Result<Frame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Result<Frame>>> {
+        self.frames.queue.poll_next(cx)
+    }
+}
+
+impl Queue {
+    fn poll_next(&self, cx: &mut Context) -> Poll<Option<Result<Frame>>> {
+        let mut state = self.lock();
+        match state.frames.pop_front() {
+            Some(frame) => {
+                state.waker = None;
+                drop(state);
+                self.not_full.notify_one();
+                Poll::Ready(Some(Ok(frame)))
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a> Deref for FrameStream<'a> {
+    type Target = Frames<'a>;
+    fn deref(&self) -> &Frames<'a> {
+        &self.frames
+    }
+}
+
+impl<'a> DerefMut for FrameStream<'a> {
+    fn deref_mut(&mut self) -> &mut Frames<'a> {
+        &mut self.frames
+    }
+}
+
+impl Deref for SharedFrameStream {
+    type Target = SharedFrames;
+    fn deref(&self) -> &SharedFrames {
+        &self.frames
+    }
+}
+
+impl DerefMut for SharedFrameStream {
+    fn deref_mut(&mut self) -> &mut SharedFrames {
+        &mut self.frames
+    }
+}
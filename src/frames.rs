@@ -0,0 +1,270 @@
+//! Pull based frame delivery, on top of the callback based outputs.
+
+use crate::{
+    c, Camera, Error, ErrorKind, Format, Frame, OutputKind, OutputStream, Result,
+    VideoEncoderConfig,
+};
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+/// What to do with a new frame when the queue of [`Frames`] is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest frame in the queue to make room for the new one.
+    DropOldest,
+    /// Drop the new frame.
+    DropNewest,
+    /// Block the callback thread until there is room in the queue.
+    ///
+    /// Note that this stalls the camera pipeline while the queue is full.
+    Block,
+}
+
+/// Iterator over the frames of an output, returned by [`Camera::frames`].
+///
+/// Frames are queued by the callback thread, and can be taken out at any pace.
+/// Stops the output when dropped.
+/// Dereferences to the [`Camera`], to allow changing controls while the output is running.
+pub struct Frames<'a> {
+    pub(crate) queue: Arc<Queue>,
+    timeout: Option<Duration>,
+    stream: OutputStream<'a>,
+}
+
+pub(crate) struct Queue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    pub(crate) not_full: Condvar,
+}
+
+pub(crate) struct QueueState {
+    pub(crate) frames: VecDeque<Frame>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: u64,
+    closed: bool,
+    /// Waker of the task waiting for a frame, if any.
+    pub(crate) waker: Option<Waker>,
+}
+
+impl Queue {
+    /// A new, empty queue, with the [`DropOldest`][OverflowPolicy::DropOldest] policy.
+    pub(crate) fn new(capacity: usize) -> Result<Arc<Self>> {
+        if capacity == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidArgument,
+                "Camera::frames",
+                "capacity must be non-zero",
+            ));
+        }
+        Ok(Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                frames: VecDeque::with_capacity(capacity),
+                capacity,
+                policy: OverflowPolicy::DropOldest,
+                dropped: 0,
+                closed: false,
+                waker: None,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }))
+    }
+
+    /// The output callback that pushes frames of the given kind and format onto the queue.
+    pub(crate) fn producer(
+        self: &Arc<Self>,
+        kind: OutputKind,
+        format: Format,
+    ) -> impl FnMut(&c::Buffer) + Send + 'static {
+        let queue = self.clone();
+        move |buffer| queue.push(Frame::from_raw_buffer(kind, buffer, format))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, frame: Frame) {
+        let mut state = self.lock();
+        while state.frames.len() >= state.capacity && !state.closed {
+            match state.policy {
+                OverflowPolicy::DropOldest => {
+                    state.frames.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return;
+                }
+                OverflowPolicy::Block => {
+                    state = self.not_full.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+        if state.closed {
+            return;
+        }
+        state.frames.push_back(frame);
+        let waker = state.waker.take();
+        drop(state);
+        self.not_empty.notify_one();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.not_full.notify_all();
+    }
+
+    pub(crate) fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.lock().policy = policy;
+        self.not_full.notify_all();
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    pub(crate) fn queued(&self) -> usize {
+        self.lock().frames.len()
+    }
+
+    /// Wait for the next frame, returning a timeout error for `operation` after `timeout`.
+    pub(crate) fn pop(&self, timeout: Option<Duration>, operation: &'static str) -> Result<Frame> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut state = self.lock();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.not_full.notify_one();
+                return Ok(frame);
+            }
+            state = match deadline {
+                None => self
+                    .not_empty
+                    .wait(state)
+                    .unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(Error::new(ErrorKind::Timeout, operation));
+                    }
+                    self.not_empty
+                        .wait_timeout(state, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+}
+
+impl Camera {
+    /// Start the given output, and return an iterator over its frames.
+    ///
+    /// At most `capacity` frames are queued. What happens when the queue is full
+    /// is determined by the [`OverflowPolicy`], which is [`DropOldest`][OverflowPolicy::DropOldest] by default.
+    ///
+    /// [`OutputKind::Video`] uses the default [`VideoEncoderConfig`].
+    /// Use [`Camera::video_frames`] to specify a different configuration.
+    pub fn frames(&mut self, kind: OutputKind, capacity: usize) -> Result<Frames<'_>> {
+        let encoder_state = match kind {
+            OutputKind::Video => Some(VideoEncoderConfig::default().into()),
+            _ => None,
+        };
+        self.start_frames(kind, encoder_state, capacity)
+    }
+
+    /// Start encoding video, and return an iterator over the encoded data.
+    ///
+    /// See [`Camera::frames`].
+    pub fn video_frames(
+        &mut self,
+        config: VideoEncoderConfig,
+        capacity: usize,
+    ) -> Result<Frames<'_>> {
+        self.start_frames(OutputKind::Video, Some(config.into()), capacity)
+    }
+
+    fn start_frames(
+        &mut self,
+        kind: OutputKind,
+        encoder_state: Option<c::VideoEncoderState>,
+        capacity: usize,
+    ) -> Result<Frames<'_>> {
+        let queue = Queue::new(capacity)?;
+        let format = self.get_format()?;
+        let stream = self.start_output(kind, encoder_state, queue.producer(kind, format))?;
+        Ok(Frames {
+            queue,
+            timeout: None,
+            stream,
+        })
+    }
+}
+
+impl Frames<'_> {
+    /// Set what to do with new frames when the queue is full.
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.queue.set_overflow_policy(policy);
+    }
+
+    /// Set how long [`next`][Iterator::next] waits for a frame before returning a timeout error.
+    ///
+    /// By default (`None`), it waits forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// The total number of frames dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// The number of frames currently in the queue.
+    pub fn queued(&self) -> usize {
+        self.queue.queued()
+    }
+
+    /// Stop the output.
+    ///
+    /// Same as dropping the iterator, but reports errors.
+    pub fn stop(mut self) -> Result<()> {
+        self.queue.close();
+        let kind = self.stream.kind();
+        self.stream.stop_output(kind)
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Result<Frame>> {
+        Some(self.queue.pop(self.timeout, "Frames::next"))
+    }
+}
+
+impl Deref for Frames<'_> {
+    type Target = Camera;
+    fn deref(&self) -> &Camera {
+        &self.stream
+    }
+}
+
+impl DerefMut for Frames<'_> {
+    fn deref_mut(&mut self) -> &mut Camera {
+        &mut self.stream
+    }
+}
+
+impl Drop for Frames<'_> {
+    fn drop(&mut self) {
+        // Unblock the callback thread before the stream is stopped.
+        self.queue.close();
+    }
+}
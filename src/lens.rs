@@ -0,0 +1,471 @@
+//! Lens shading correction tables.
+
+use crate::{c, to_result, Camera, Error, ErrorKind, Result};
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Size in sensor pixels of the (square) cells of the lens shading grid.
+///
+/// This is 32 pixels in each of the half resolution Bayer channels.
+pub const LENS_SHADING_CELL_SIZE: u32 = 64;
+
+/// Gain value representing a gain of 1.0.
+pub const LENS_SHADING_UNITY_GAIN: u8 = 32;
+
+/// A lens shading correction table.
+///
+/// The table contains a grid of gains for each of the four Bayer channels,
+/// with one gain per cell of 64x64 sensor pixels. A gain of 32 means 1.0.
+///
+/// The on-disk format is the C header format used by the library and by
+/// `lens_shading_analyse`:
+///
+/// ```text
+/// uint8_t ls_grid[] = {
+/// //Ch 3
+/// 32, 32, ...
+/// ...
+/// //Ch 0
+/// 32, 32, ...
+/// };
+/// uint32_t ref_transform = 3;
+/// uint32_t grid_width = 41;
+/// uint32_t grid_height = 31;
+/// ```
+///
+/// Channels are stored in the file from channel 3 down to channel 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LensShadingTable {
+    grid_width: u32,
+    grid_height: u32,
+    ref_transform: u32,
+    /// Gains per channel, indexed by channel number, in row-major order.
+    channels: [Vec<u8>; 4],
+}
+
+impl LensShadingTable {
+    /// Create a table with all gains set to 1.0.
+    ///
+    /// Returns an error of kind [`InvalidArgument`][ErrorKind::InvalidArgument]
+    /// if the number of cells does not fit in a `usize`.
+    pub fn new(grid_width: u32, grid_height: u32, ref_transform: u32) -> Result<Self> {
+        let size = grid_size(grid_width, grid_height).ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::InvalidArgument,
+                "LensShadingTable::new",
+                format!("grid of {}x{} cells is too large", grid_width, grid_height),
+            )
+        })?;
+        let channel = vec![LENS_SHADING_UNITY_GAIN; size];
+        Ok(Self {
+            grid_width,
+            grid_height,
+            ref_transform,
+            channels: [channel.clone(), channel.clone(), channel.clone(), channel],
+        })
+    }
+
+    /// Create a table with all gains set to 1.0, with a grid that covers the given
+    /// sensor resolution, in cells of [`LENS_SHADING_CELL_SIZE`] pixels.
+    ///
+    /// See [`new`][LensShadingTable::new].
+    pub fn for_resolution(width: u32, height: u32, ref_transform: u32) -> Result<Self> {
+        Self::new(
+            width.div_ceil(LENS_SHADING_CELL_SIZE),
+            height.div_ceil(LENS_SHADING_CELL_SIZE),
+            ref_transform,
+        )
+    }
+
+    /// The number of cells in the horizontal direction.
+    pub fn grid_width(&self) -> u32 {
+        self.grid_width
+    }
+
+    /// The number of cells in the vertical direction.
+    pub fn grid_height(&self) -> u32 {
+        self.grid_height
+    }
+
+    /// The transform (flip/rotation) of the sensor the table was made for.
+    pub fn ref_transform(&self) -> u32 {
+        self.ref_transform
+    }
+
+    /// The gains of one channel (0 to 3), in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is not in the range 0 to 3.
+    pub fn channel(&self, channel: usize) -> &[u8] {
+        &self.channels[channel]
+    }
+
+    /// Mutable access to the gains of one channel (0 to 3), in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is not in the range 0 to 3.
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [u8] {
+        &mut self.channels[channel]
+    }
+
+    /// The gain of one cell of one channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is not in the range 0 to 3, or the cell is outside the grid.
+    pub fn gain(&self, channel: usize, x: u32, y: u32) -> u8 {
+        self.channels[channel][self.index(x, y)]
+    }
+
+    /// Set the gain of one cell of one channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is not in the range 0 to 3, or the cell is outside the grid.
+    pub fn set_gain(&mut self, channel: usize, x: u32, y: u32, gain: u8) {
+        let index = self.index(x, y);
+        self.channels[channel][index] = gain;
+    }
+
+    /// Check that the table covers the given sensor resolution exactly,
+    /// in cells of [`LENS_SHADING_CELL_SIZE`] pixels.
+    pub fn check_resolution(&self, width: u32, height: u32) -> Result<()> {
+        let expected = (
+            width.div_ceil(LENS_SHADING_CELL_SIZE),
+            height.div_ceil(LENS_SHADING_CELL_SIZE),
+        );
+        if (self.grid_width, self.grid_height) != expected {
+            return Err(Error::with_message(
+                ErrorKind::InvalidArgument,
+                "LensShadingTable::check_resolution",
+                format!(
+                    "grid of {}x{} cells does not match resolution {}x{} (expected {}x{} cells)",
+                    self.grid_width, self.grid_height, width, height, expected.0, expected.1
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse a table from the on-disk format.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = strip_comments(text);
+        let grid_start = text
+            .find("ls_grid")
+            .ok_or_else(|| invalid("missing ls_grid"))?;
+        let open = grid_start
+            + text[grid_start..]
+                .find('{')
+                .ok_or_else(|| invalid("missing '{' after ls_grid"))?;
+        let close = open
+            + text[open..]
+                .find('}')
+                .ok_or_else(|| invalid("missing '}' after ls_grid"))?;
+        let values = text[open + 1..close]
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                parse_int(v)
+                    .and_then(|v| u8::try_from(v).ok())
+                    .ok_or_else(|| invalid(format!("invalid gain: {:?}", v)))
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        let rest = &text[close..];
+        let grid_width = parse_variable(rest, "grid_width")?;
+        let grid_height = parse_variable(rest, "grid_height")?;
+        let ref_transform = parse_variable(rest, "ref_transform")?;
+        if grid_width == 0 || grid_height == 0 {
+            return Err(invalid("empty grid"));
+        }
+        if ref_transform > 7 {
+            return Err(invalid(format!("invalid ref_transform: {}", ref_transform)));
+        }
+        let too_large = || {
+            invalid(format!(
+                "grid of {}x{} cells is too large",
+                grid_width, grid_height
+            ))
+        };
+        let size = grid_size(grid_width, grid_height).ok_or_else(too_large)?;
+        let gains = size.checked_mul(4).ok_or_else(too_large)?;
+        if values.len() != gains {
+            return Err(invalid(format!(
+                "expected {} gains for a grid of {}x{} cells, found {}",
+                gains,
+                grid_width,
+                grid_height,
+                values.len()
+            )));
+        }
+        // Channel 3 comes first.
+        let mut chunks = values.chunks(size).map(<[u8]>::to_vec);
+        let ch3 = chunks.next().unwrap_or_default();
+        let ch2 = chunks.next().unwrap_or_default();
+        let ch1 = chunks.next().unwrap_or_default();
+        let ch0 = chunks.next().unwrap_or_default();
+        Ok(Self {
+            grid_width,
+            grid_height,
+            ref_transform,
+            channels: [ch0, ch1, ch2, ch3],
+        })
+    }
+
+    /// Read a table from a file in the on-disk format.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            Error::with_message(ErrorKind::Io, "LensShadingTable::load", e.to_string())
+        })?;
+        Self::parse(&text)
+    }
+
+    /// Write the table to a file in the on-disk format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_string()).map_err(|e| {
+            Error::with_message(ErrorKind::Io, "LensShadingTable::save", e.to_string())
+        })
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.grid_width && y < self.grid_height);
+        y as usize * self.grid_width as usize + x as usize
+    }
+}
+
+impl FromStr for LensShadingTable {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for LensShadingTable {
+    /// Formats the table in the on-disk format.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "uint8_t ls_grid[] = {{")?;
+        for channel in (0..4).rev() {
+            writeln!(f, "//Ch {}", channel)?;
+            for row in self.channels[channel].chunks(self.grid_width.max(1) as usize) {
+                for gain in row {
+                    write!(f, "{}, ", gain)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        writeln!(f, "}};")?;
+        writeln!(f, "uint32_t ref_transform = {};", self.ref_transform)?;
+        writeln!(f, "uint32_t grid_width = {};", self.grid_width)?;
+        writeln!(f, "uint32_t grid_height = {};", self.grid_height)
+    }
+}
+
+impl Camera {
+    /// Set the lens shading table file to use.
+    pub fn set_lens_table(&mut self, path: &Path) -> Result<()> {
+        let path = path_to_cstring(path, "arducam_set_lens_table")?;
+        unsafe {
+            to_result(
+                c::arducam_set_lens_table(self.ptr, path.as_ptr() as *mut _),
+                "arducam_set_lens_table",
+            )?;
+        }
+        self.lens_table = Some(path);
+        Ok(())
+    }
+}
+
+/// Convert a path to a C string, for the functions that take a path.
+pub(crate) fn path_to_cstring(path: &Path, operation: &'static str) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        Error::with_message(
+            ErrorKind::InvalidArgument,
+            operation,
+            "path contains a nul byte",
+        )
+    })
+}
+
+/// The number of cells of a grid, if it fits in a `usize`.
+fn grid_size(grid_width: u32, grid_height: u32) -> Option<usize> {
+    usize::try_from(grid_width)
+        .ok()?
+        .checked_mul(usize::try_from(grid_height).ok()?)
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::with_message(
+        ErrorKind::InvalidArgument,
+        "LensShadingTable::parse",
+        message,
+    )
+}
+
+/// Remove `//` and `/* */` comments.
+fn strip_comments(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let line = rest.find("//");
+        let block = rest.find("/*");
+        match (line, block) {
+            (Some(l), Some(b)) if b < l => {
+                result.push_str(&rest[..b]);
+                result.push(' ');
+                rest = rest[b..].find("*/").map_or("", |e| &rest[b + e + 2..]);
+            }
+            (None, Some(b)) => {
+                result.push_str(&rest[..b]);
+                result.push(' ');
+                rest = rest[b..].find("*/").map_or("", |e| &rest[b + e + 2..]);
+            }
+            (Some(l), _) => {
+                result.push_str(&rest[..l]);
+                rest = rest[l..].find('\n').map_or("", |n| &rest[l + n..]);
+            }
+            (None, None) => {
+                result.push_str(rest);
+                return result;
+            }
+        }
+    }
+}
+
+/// Parse a decimal or hexadecimal (`0x`) integer, with an optional C suffix (`u`).
+pub(crate) fn parse_int(s: &str) -> Option<u32> {
+    let s = s.trim_end_matches(['u', 'U']);
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Find `name = value;` and parse the value.
+fn parse_variable(text: &str, name: &str) -> Result<u32> {
+    let start = text
+        .find(name)
+        .ok_or_else(|| invalid(format!("missing {}", name)))?;
+    let rest = &text[start + name.len()..];
+    let rest = rest
+        .trim_start()
+        .strip_prefix('=')
+        .ok_or_else(|| invalid(format!("missing '=' after {}", name)))?;
+    let end = rest
+        .find(';')
+        .ok_or_else(|| invalid(format!("missing ';' after {}", name)))?;
+    parse_int(rest[..end].trim()).ok_or_else(|| invalid(format!("invalid value for {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "
+        // Generated by lens_shading_analyse
+        uint8_t ls_grid[] = {
+        //Ch 3
+        30, 31, 0x20,
+        40, 41, 42,
+        //Ch 2
+        32, 32, 32,
+        32, 32, 32,
+        /* Ch 1 */
+        1, 2, 3,
+        4, 5, 6u,
+        //Ch 0
+        255, 0, 32,
+        32, 32, 32,
+        };
+        uint32_t ref_transform = 3;
+        uint32_t grid_width = 3;
+        uint32_t grid_height = 2;
+    ";
+
+    #[test]
+    fn parse() {
+        let table = LensShadingTable::parse(TABLE).unwrap();
+        assert_eq!((table.grid_width(), table.grid_height()), (3, 2));
+        assert_eq!(table.ref_transform(), 3);
+        assert_eq!(table.channel(3), &[30, 31, 32, 40, 41, 42]);
+        assert_eq!(table.channel(1), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(table.gain(0, 0, 0), 255);
+        assert_eq!(table.gain(3, 2, 1), 42);
+        assert_eq!(TABLE.parse::<LensShadingTable>().unwrap(), table);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let mut table = LensShadingTable::new(5, 4, 1).unwrap();
+        for channel in 0..4 {
+            for (i, gain) in table.channel_mut(channel).iter_mut().enumerate() {
+                *gain = (channel * 50 + i) as u8;
+            }
+        }
+        table.set_gain(2, 4, 3, 7);
+        let text = table.to_string();
+        assert!(text.starts_with("uint8_t ls_grid[] = {\n//Ch 3\n150, 151,"));
+        assert_eq!(LensShadingTable::parse(&text).unwrap(), table);
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let short = TABLE.replace("255, 0, 32,", "255, 0,");
+        let e = LensShadingTable::parse(&short).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+        assert_eq!(e.operation(), "LensShadingTable::parse");
+        let wide = TABLE.replace("grid_width = 3", "grid_width = 4");
+        assert!(LensShadingTable::parse(&wide).is_err());
+        let empty = TABLE.replace("grid_height = 2", "grid_height = 0");
+        assert!(LensShadingTable::parse(&empty).is_err());
+        let huge = TABLE
+            .replace("grid_width = 3", "grid_width = 4294967295")
+            .replace("grid_height = 2", "grid_height = 4294967295");
+        let e = LensShadingTable::parse(&huge).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+    }
+
+    #[test]
+    fn bad_values() {
+        for (from, to) in [
+            ("40, 41", "40, 256"),
+            ("40, 41", "40, -1"),
+            ("40, 41", "40, x"),
+            ("ref_transform = 3", "ref_transform = 8"),
+            ("grid_width = 3", "grid_width = three"),
+            ("uint32_t grid_height = 2;", ""),
+            ("};", ""),
+        ] {
+            let text = TABLE.replace(from, to);
+            let e = LensShadingTable::parse(&text).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidArgument, "{:?}", to);
+        }
+    }
+
+    #[test]
+    fn resolution() {
+        // The OV5647 and IMX219 tables of the library.
+        let table = LensShadingTable::for_resolution(2592, 1944, 0).unwrap();
+        assert_eq!((table.grid_width(), table.grid_height()), (41, 31));
+        table.check_resolution(2592, 1944).unwrap();
+        let table = LensShadingTable::for_resolution(3280, 2464, 0).unwrap();
+        assert_eq!((table.grid_width(), table.grid_height()), (52, 39));
+        assert!(table
+            .channel(0)
+            .iter()
+            .all(|&g| g == LENS_SHADING_UNITY_GAIN));
+        let e = table.check_resolution(1920, 1080).unwrap_err();
+        assert_eq!(e.operation(), "LensShadingTable::check_resolution");
+    }
+
+    #[test]
+    #[should_panic]
+    fn gain_outside_grid() {
+        LensShadingTable::new(3, 2, 0).unwrap().gain(0, 3, 0);
+    }
+}
@@ -1,32 +1,102 @@
 //! Rust wrapper for the [ArduCAM MIPI Camera library](https://github.com/ArduCAM/MIPI_Camera/tree/master/RPI).
 
 pub mod c;
+pub mod debayer;
+pub mod sensor;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+pub mod unpack;
 
+mod callback;
+mod control;
+#[cfg(all(feature = "dlopen", not(feature = "simulator")))]
+mod dynamic;
+mod encoder;
+mod error;
+mod flags;
+mod format;
+mod frame;
+#[cfg(feature = "stream")]
+mod frame_stream;
+mod frames;
+mod lens;
+mod output;
+mod preview;
+mod raw;
+mod register;
+mod shared;
+mod test_pattern;
+mod video;
+mod yuv;
+
+pub use control::{Control, ControlInfo};
+pub use debayer::CfaPattern;
+#[cfg(all(feature = "dlopen", not(feature = "simulator")))]
+pub use dynamic::{load_library, DEFAULT_LIBRARY};
+pub use encoder::{
+    H264Level, H264Profile, IntraRefresh, RateControl, VideoCodec, VideoEncoderConfig,
+    VideoEncoderConfigBuilder,
+};
+pub use error::{Error, ErrorKind, Result};
+pub use flags::BufferFlags;
+pub use format::{Packing, PixelFormat, SensorMode};
+pub use frame::{Frame, FrameLayout, FrameSource, PixelLayout};
+#[cfg(feature = "stream")]
+pub use frame_stream::{FrameStream, SharedFrameStream};
+pub use frames::{Frames, OverflowPolicy};
+pub use lens::{LensShadingTable, LENS_SHADING_CELL_SIZE, LENS_SHADING_UNITY_GAIN};
+pub use output::{OutputKind, OutputStream};
+pub use preview::{PreviewGuard, PreviewOptions};
+pub use raw::RawFrame;
+pub use register::{RegisterTable, RegisterWrite};
+pub use shared::{SharedCamera, SharedCameraGuard, SharedFrames};
+pub use test_pattern::TestPattern;
+pub use video::VideoChunk;
+pub use yuv::YuvFrame;
+
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 
 /// Interface to a camera.
+///
+/// The camera is closed when dropped, logging any errors. Use [`Camera::close`] to handle them instead.
+///
+/// A camera can be moved to another thread, but not used from several threads at once.
+/// Use [`SharedCamera`] for that.
 pub struct Camera {
     ptr: c::CameraInstance,
+    outputs: output::Outputs,
+    preview: bool,
+    /// The path of the lens shading table, kept alive for the library.
+    lens_table: Option<std::ffi::CString>,
 }
 
-/// Buffer returned by [`Camera::capture`].
-pub struct Buffer {
+// The library can be used from any thread, as long as calls for the same
+// camera don't overlap, which `&mut self` ensures. The output callbacks are `Send`.
+// See the `shared` module.
+unsafe impl Send for Camera {}
+
+/// Buffer returned by [`Camera::capture_buffer`], and held by the [`Frame`] returned by [`Camera::capture`].
+///
+/// The buffer borrows the camera it came from, since it must be released before the camera is closed:
+///
+/// ```compile_fail,E0505
+/// # use arducam_mipicamera::{Camera, Encoding};
+/// let camera = Camera::init(None).unwrap();
+/// let buffer = camera.capture_buffer(1000, Encoding::Jpeg, 90).unwrap();
+/// drop(camera); // Error: `camera` is still borrowed by `buffer`.
+/// println!("{} bytes", buffer.data().len());
+/// ```
+#[derive(Debug)]
+pub struct Buffer<'cam> {
     ptr: *mut c::Buffer,
+    camera: PhantomData<&'cam Camera>,
 }
 
 pub use c::CameraInterface;
 pub use c::Format;
 pub use c::Fract;
-
-// TODO: Wrap Format struct:
-//   pub struct Format {
-//       mode: i32,
-//       width: i32,
-//       height: i32,
-//       pixel_format: ???,
-//       frame_intervals: ???,
-//       description: &'static CStr,
-//   }
+pub use c::Rectangle;
 
 /// Image encoding format.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -43,49 +113,79 @@ impl Camera {
     /// Initialize a camera.
     ///
     /// Optionally, specific interface settings can be given.
-    pub fn init(interface: Option<CameraInterface>) -> Result<Self, ()> {
+    ///
+    /// With the `dlopen` feature, this loads the library first if `load_library` was not called,
+    /// and returns an error of kind [`LibraryNotFound`][ErrorKind::LibraryNotFound] or
+    /// [`SymbolMissing`][ErrorKind::SymbolMissing] if that fails.
+    pub fn init(interface: Option<CameraInterface>) -> Result<Self> {
+        #[cfg(all(feature = "dlopen", not(feature = "simulator")))]
+        dynamic::load_default()?;
         let mut ptr: c::CameraInstance = std::ptr::null_mut();
-        let r = if let Some(interface) = interface {
-            unsafe { c::arducam_init_camera2(&mut ptr, interface) }
+        if let Some(interface) = interface {
+            unsafe {
+                to_result(
+                    c::arducam_init_camera2(&mut ptr, interface),
+                    "arducam_init_camera2",
+                )?
+            }
         } else {
-            unsafe { c::arducam_init_camera(&mut ptr) }
+            unsafe { to_result(c::arducam_init_camera(&mut ptr), "arducam_init_camera")? }
         };
-        if r == 0 {
-            Ok(Self { ptr })
-        } else {
-            Err(())
-        }
+        Ok(Self {
+            ptr,
+            outputs: Default::default(),
+            preview: false,
+            lens_table: None,
+        })
     }
 
     /// Set the output resolution.
-    pub fn set_resolution(&mut self, mut width: i32, mut height: i32) -> Result<(i32, i32), ()> {
+    ///
+    /// Returns an error of kind [`Busy`][ErrorKind::Busy] while an output is running,
+    /// since its frames are tagged with the format at the time it was started.
+    pub fn set_resolution(&mut self, mut width: i32, mut height: i32) -> Result<(i32, i32)> {
+        self.check_no_outputs("arducam_set_resolution")?;
         unsafe {
-            to_result(c::arducam_set_resolution(self.ptr, &mut width, &mut height))?;
+            to_result(
+                c::arducam_set_resolution(self.ptr, &mut width, &mut height),
+                "arducam_set_resolution",
+            )?;
             Ok((width, height))
         }
     }
 
     /// Set the mode of the sensor.
-    pub fn set_mode(&mut self, mode: i32) -> Result<(), ()> {
-        unsafe { to_result(c::arducam_set_mode(self.ptr, mode)) }
+    ///
+    /// Returns an error of kind [`Busy`][ErrorKind::Busy] while an output is running,
+    /// like [`set_resolution`][Camera::set_resolution].
+    pub fn set_mode(&mut self, mode: i32) -> Result<()> {
+        self.check_no_outputs("arducam_set_mode")?;
+        unsafe { to_result(c::arducam_set_mode(self.ptr, mode), "arducam_set_mode") }
     }
 
     /// Get the current output format.
-    pub fn get_format(&mut self) -> Result<Format, ()> {
+    pub fn get_format(&self) -> Result<Format> {
         unsafe {
             let mut format = MaybeUninit::uninit();
-            to_result(c::arducam_get_format(self.ptr, format.as_mut_ptr()))?;
+            to_result(
+                c::arducam_get_format(self.ptr, format.as_mut_ptr()),
+                "arducam_get_format",
+            )?;
             Ok(format.assume_init())
         }
     }
 
-    /// Capture a single frame.
-    pub fn capture(
-        &mut self,
+    /// Capture a single frame, without the format information of [`capture`][Camera::capture].
+    ///
+    /// The library does not report why a capture failed: it might have timed out,
+    /// the encoding might not be supported, or something else went wrong.
+    /// Such failures are reported as an error of kind [`Other`][ErrorKind::Other].
+    pub fn capture_buffer(
+        &self,
         timeout: i32,
         encoding: Encoding,
         quality: i32,
-    ) -> Result<Buffer, ()> {
+    ) -> Result<Buffer<'_>> {
         unsafe {
             let mut format = c::ImageFormat {
                 encoding: encoding as u32,
@@ -93,43 +193,68 @@ impl Camera {
             };
             let buffer = c::arducam_capture(self.ptr, &mut format, timeout);
             if buffer.is_null() {
-                return Err(());
+                return Err(Error::with_message(
+                    ErrorKind::Other,
+                    "arducam_capture",
+                    "no buffer returned (timeout, unsupported encoding, or internal failure)",
+                ));
             }
             Ok(Buffer::from_raw_pointer(buffer))
         }
     }
 
     /// Set a camera control to default value.
-    pub fn reset_control(&mut self, ctrl_id: i32) -> Result<(), ()> {
-        unsafe { to_result(c::arducam_reset_control(self.ptr, ctrl_id)) }
+    pub fn reset_control(&mut self, control: impl Into<Control>) -> Result<()> {
+        let ctrl_id = control.into().id();
+        unsafe {
+            to_result(
+                c::arducam_reset_control(self.ptr, ctrl_id),
+                "arducam_reset_control",
+            )
+        }
     }
 
     /// Set a camera control to the specified value.
-    pub fn set_control(&mut self, ctrl_id: i32, value: i32) -> Result<(), ()> {
-        unsafe { to_result(c::arducam_set_control(self.ptr, ctrl_id, value)) }
+    pub fn set_control(&mut self, control: impl Into<Control>, value: i32) -> Result<()> {
+        let ctrl_id = control.into().id();
+        unsafe {
+            to_result(
+                c::arducam_set_control(self.ptr, ctrl_id, value),
+                "arducam_set_control",
+            )
+        }
     }
 
     /// Read the current value of a camera control.
-    pub fn get_control(&mut self, ctrl_id: i32) -> Result<i32, ()> {
+    pub fn get_control(&mut self, control: impl Into<Control>) -> Result<i32> {
+        let ctrl_id = control.into().id();
         unsafe {
             let mut value = 0;
-            to_result(c::arducam_get_control(self.ptr, ctrl_id, &mut value))?;
+            to_result(
+                c::arducam_get_control(self.ptr, ctrl_id, &mut value),
+                "arducam_get_control",
+            )?;
             Ok(value)
         }
     }
 
     /// Enable or disable software auto exposure.
-    pub fn arducam_software_auto_exposure(&mut self, enable: bool) -> Result<(), ()> {
-        unsafe { to_result(c::arducam_software_auto_exposure(self.ptr, enable as i32)) }
+    pub fn arducam_software_auto_exposure(&mut self, enable: bool) -> Result<()> {
+        unsafe {
+            to_result(
+                c::arducam_software_auto_exposure(self.ptr, enable as i32),
+                "arducam_software_auto_exposure",
+            )
+        }
     }
 
     /// Enable or disable software auto white balance.
-    pub fn arducam_software_auto_white_balance(&mut self, enable: bool) -> Result<(), ()> {
+    pub fn arducam_software_auto_white_balance(&mut self, enable: bool) -> Result<()> {
         unsafe {
-            to_result(c::arducam_software_auto_white_balance(
-                self.ptr,
-                enable as i32,
-            ))
+            to_result(
+                c::arducam_software_auto_white_balance(self.ptr, enable as i32),
+                "arducam_software_auto_white_balance",
+            )
         }
     }
 
@@ -138,44 +263,88 @@ impl Camera {
         self.ptr
     }
 
+    /// Close the camera.
+    ///
+    /// Stops the preview and all outputs first. Same as dropping the camera, but reports errors.
+    /// The camera is closed even if stopping the preview or an output failed.
+    pub fn close(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    /// Stop the preview and all outputs, and close the camera, if that wasn't done already.
+    ///
+    /// Callbacks that could not be unregistered are kept alive until the
+    /// camera is dropped, or leaked if closing the camera failed, since the
+    /// library might still call them.
+    fn shutdown(&mut self) -> Result<()> {
+        if self.ptr.is_null() {
+            return Ok(());
+        }
+        let preview = self.stop_preview();
+        let outputs = self.stop_all_outputs();
+        let close = unsafe { to_result(c::arducam_close_camera(self.ptr), "arducam_close_camera") };
+        self.ptr = std::ptr::null_mut();
+        if close.is_err() {
+            std::mem::forget(std::mem::take(&mut self.outputs));
+            std::mem::forget(self.lens_table.take());
+        }
+        preview.and(outputs).and(close)
+    }
+
     // TODO:
-    //  - start_preview
-    //  - stop_preview
-    //  - set_video_callback
-    //  - set_raw_callback
-    //  - get_support_formats
-    //  - get_support_controls
     //  - manual_set_awb_compensation
-    //  - read_sensor_reg
-    //  - write_sensor_reg
-    //  - set_lens_table
-    //  - set_yuv_callback
-    //  - start_preview_fix_lens
     //  - get_gain
 }
 
 impl Drop for Camera {
     fn drop(&mut self) {
-        unsafe { to_result(c::arducam_close_camera(self.ptr)).unwrap() };
+        if let Err(e) = self.shutdown() {
+            log::error!("failed to close camera: {}", e);
+        }
     }
 }
 
-impl Buffer {
+impl<'cam> Buffer<'cam> {
     /// The data contained in the buffer.
     pub fn data(&self) -> &[u8] {
-        unsafe {
-            std::slice::from_raw_parts(self.raw_buffer().data, self.raw_buffer().length as usize)
-        }
+        unsafe { self.raw_buffer().bytes() }
     }
 
     /// The presentation timestamp of the buffer.
     ///
     /// Returns `None` when the buffer timestamp is set to `TIME_UNKNOWN`.
     pub fn timestamp(&self) -> Option<i64> {
-        match self.raw_buffer().pts {
-            c::TIME_UNKNOWN => None,
-            t => Some(t),
-        }
+        self.raw_buffer().timestamp()
+    }
+
+    /// The metadata flags of the buffer.
+    pub fn flags(&self) -> BufferFlags {
+        BufferFlags::from_bits_retain(self.raw_buffer().flags)
+    }
+
+    /// The buffer is (part of) a keyframe.
+    pub fn is_keyframe(&self) -> bool {
+        self.flags().is_keyframe()
+    }
+
+    /// The buffer contains codec config data, not a frame.
+    pub fn is_config(&self) -> bool {
+        self.flags().is_config()
+    }
+
+    /// The data is known to be corrupted, or failed to be transmitted.
+    pub fn is_corrupted(&self) -> bool {
+        self.flags().is_corrupted()
+    }
+
+    /// The buffer ends a frame.
+    pub fn is_frame_end(&self) -> bool {
+        self.flags().is_frame_end()
+    }
+
+    /// The buffer is the end of the stream.
+    pub fn is_eos(&self) -> bool {
+        self.flags().is_eos()
     }
 
     /// Access to the raw [`Buffer`][c::Buffer] structure.
@@ -184,6 +353,10 @@ impl Buffer {
     }
 
     /// Mutable access to the raw [`Buffer`][c::Buffer] structure.
+    ///
+    /// # Safety
+    ///
+    /// The `data` pointer and `length` must remain valid for the buffer.
     pub unsafe fn raw_buffer_mut(&mut self) -> &mut c::Buffer {
         &mut *self.ptr
     }
@@ -195,22 +368,39 @@ impl Buffer {
 
     /// Take ownership of a raw [`Buffer`][c::Buffer].
     ///
-    /// The pointer must be valid and non-null.
+    /// # Safety
+    ///
+    /// The pointer must be valid and non-null, and must not be released elsewhere.
+    /// The camera it came from must not be closed for the lifetime `'cam`.
     pub unsafe fn from_raw_pointer(ptr: *mut c::Buffer) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            camera: PhantomData,
+        }
+    }
+}
+
+impl AsRef<[u8]> for Buffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.data()
     }
 }
 
-impl Drop for Buffer {
+impl Drop for Buffer<'_> {
     fn drop(&mut self) {
         unsafe { c::arducam_release_buffer(self.ptr) };
     }
 }
 
-fn to_result(r: i32) -> Result<(), ()> {
+fn to_result(r: i32, operation: &'static str) -> Result<()> {
     if r == 0 {
         Ok(())
     } else {
-        Err(())
+        Err(Error::from_code(r, operation))
     }
 }
+
+/// Round `x` up to a multiple of `alignment`.
+fn align_up(x: usize, alignment: usize) -> usize {
+    x.div_ceil(alignment) * alignment
+}
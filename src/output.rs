@@ -0,0 +1,175 @@
+//! Management of the callback based outputs of the camera.
+
+use crate::callback::{trampoline, Registration};
+use crate::{c, to_result, Camera, Error, ErrorKind, Result};
+use std::ops::{Deref, DerefMut};
+
+/// The kinds of output the camera can deliver through a callback.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputKind {
+    /// Encoded video. See [`Camera::start_video`].
+    Video,
+    /// Raw Bayer frames. See [`Camera::start_raw_stream`].
+    Raw,
+    /// YUV (I420) frames. See [`Camera::start_yuv_stream`].
+    Yuv,
+}
+
+impl OutputKind {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn operation(self) -> &'static str {
+        match self {
+            OutputKind::Video => "arducam_set_video_callback",
+            OutputKind::Raw => "arducam_set_raw_callback",
+            OutputKind::Yuv => "arducam_set_yuv_callback",
+        }
+    }
+}
+
+/// Guard returned when starting an output, such as by [`Camera::start_video`].
+///
+/// Stops the output when dropped, logging errors.
+/// Dereferences to the [`Camera`], to allow changing controls while the output is running.
+pub struct OutputStream<'a> {
+    camera: &'a mut Camera,
+    kind: OutputKind,
+}
+
+/// The callbacks registered for each [`OutputKind`].
+#[derive(Default)]
+pub(crate) struct Outputs {
+    registrations: [Option<Registration>; 3],
+}
+
+impl Camera {
+    /// Register `handler` as callback for the given output, and return a guard that stops it.
+    ///
+    /// `encoder_state` is only used for [`OutputKind::Video`].
+    pub(crate) fn start_output(
+        &mut self,
+        kind: OutputKind,
+        encoder_state: Option<c::VideoEncoderState>,
+        handler: impl FnMut(&c::Buffer) + Send + 'static,
+    ) -> Result<OutputStream<'_>> {
+        self.register_output(kind, encoder_state, handler)?;
+        Ok(OutputStream { camera: self, kind })
+    }
+
+    /// Register `handler` as callback for the given output.
+    ///
+    /// The output keeps running until [`stop_output`][Camera::stop_output] is called.
+    pub(crate) fn register_output(
+        &mut self,
+        kind: OutputKind,
+        encoder_state: Option<c::VideoEncoderState>,
+        handler: impl FnMut(&c::Buffer) + Send + 'static,
+    ) -> Result<()> {
+        if self.outputs.registrations[kind.index()].is_some() {
+            return Err(Error::new(ErrorKind::Busy, kind.operation()));
+        }
+        let registration = Registration::new(handler);
+        let userdata = registration.userdata();
+        let r = unsafe {
+            match kind {
+                OutputKind::Video => {
+                    let mut state = encoder_state;
+                    let state_ptr = state
+                        .as_mut()
+                        .map_or(std::ptr::null_mut(), |s| s as *mut c::VideoEncoderState);
+                    c::arducam_set_video_callback(self.ptr, state_ptr, Some(trampoline), userdata)
+                }
+                OutputKind::Raw => {
+                    c::arducam_set_raw_callback(self.ptr, Some(trampoline), userdata)
+                }
+                OutputKind::Yuv => {
+                    c::arducam_set_yuv_callback(self.ptr, Some(trampoline), userdata)
+                }
+            }
+        };
+        to_result(r, kind.operation())?;
+        self.outputs.registrations[kind.index()] = Some(registration);
+        Ok(())
+    }
+
+    /// Stop the given output, if it is running.
+    ///
+    /// The callback is only dropped after it has been unregistered.
+    pub(crate) fn stop_output(&mut self, kind: OutputKind) -> Result<()> {
+        if self.outputs.registrations[kind.index()].is_none() {
+            return Ok(());
+        }
+        let null = std::ptr::null_mut();
+        let r = unsafe {
+            match kind {
+                OutputKind::Video => {
+                    c::arducam_set_video_callback(self.ptr, std::ptr::null_mut(), None, null)
+                }
+                OutputKind::Raw => c::arducam_set_raw_callback(self.ptr, None, null),
+                OutputKind::Yuv => c::arducam_set_yuv_callback(self.ptr, None, null),
+            }
+        };
+        to_result(r, kind.operation())?;
+        self.outputs.registrations[kind.index()] = None;
+        Ok(())
+    }
+
+    /// Return an error of kind [`Busy`][ErrorKind::Busy] if any output is running.
+    pub(crate) fn check_no_outputs(&self, operation: &'static str) -> Result<()> {
+        if self.outputs.registrations.iter().any(Option::is_some) {
+            return Err(Error::with_message(
+                ErrorKind::Busy,
+                operation,
+                "an output is running",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Stop all running outputs.
+    pub(crate) fn stop_all_outputs(&mut self) -> Result<()> {
+        let video = self.stop_output(OutputKind::Video);
+        let raw = self.stop_output(OutputKind::Raw);
+        let yuv = self.stop_output(OutputKind::Yuv);
+        video.and(raw).and(yuv)
+    }
+}
+
+impl OutputStream<'_> {
+    /// The kind of output.
+    pub fn kind(&self) -> OutputKind {
+        self.kind
+    }
+
+    /// Stop the output.
+    ///
+    /// Same as dropping the guard, but reports errors.
+    pub fn stop(self) -> Result<()> {
+        let mut this = std::mem::ManuallyDrop::new(self);
+        let kind = this.kind;
+        this.camera.stop_output(kind)
+    }
+}
+
+impl Deref for OutputStream<'_> {
+    type Target = Camera;
+    fn deref(&self) -> &Camera {
+        self.camera
+    }
+}
+
+impl DerefMut for OutputStream<'_> {
+    fn deref_mut(&mut self) -> &mut Camera {
+        self.camera
+    }
+}
+
+impl Drop for OutputStream<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.camera.stop_output(self.kind) {
+            log::error!("failed to stop {:?} output: {}", self.kind, e);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! On-screen preview.
+
+use crate::lens::path_to_cstring;
+use crate::{c, to_result, Camera, Error, ErrorKind, Rectangle, Result};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+/// Settings for the preview, used by [`Camera::start_preview`].
+#[derive(Debug, Copy, Clone)]
+pub struct PreviewOptions {
+    /// Show the preview on the whole screen, ignoring `window`.
+    pub fullscreen: bool,
+    /// Opacity of the preview, from 0 (transparent) to 255 (opaque).
+    pub opacity: u8,
+    /// Position and size of the preview window, when not fullscreen.
+    pub window: Rectangle,
+}
+
+impl Default for PreviewOptions {
+    /// Fullscreen and fully opaque.
+    fn default() -> Self {
+        Self {
+            fullscreen: true,
+            opacity: 255,
+            window: Rectangle {
+                x: 0,
+                y: 0,
+                width: 1024,
+                height: 768,
+            },
+        }
+    }
+}
+
+impl From<PreviewOptions> for c::PreviewParams {
+    fn from(options: PreviewOptions) -> Self {
+        c::PreviewParams {
+            fullscreen: options.fullscreen as i32,
+            opacity: options.opacity.into(),
+            window: options.window,
+        }
+    }
+}
+
+/// Guard returned by [`Camera::start_preview`].
+///
+/// Stops the preview when dropped, logging errors.
+/// Dereferences to the [`Camera`], to allow changing settings while the preview is shown.
+pub struct PreviewGuard<'a> {
+    camera: &'a mut Camera,
+}
+
+impl Camera {
+    /// Start showing the preview on the screen.
+    ///
+    /// Returns an error of kind [`Busy`][ErrorKind::Busy] if the preview is already running.
+    pub fn start_preview(&mut self, options: PreviewOptions) -> Result<PreviewGuard<'_>> {
+        if self.preview {
+            return Err(Error::new(ErrorKind::Busy, "arducam_start_preview"));
+        }
+        let mut params = c::PreviewParams::from(options);
+        unsafe {
+            to_result(
+                c::arducam_start_preview(self.ptr, &mut params),
+                "arducam_start_preview",
+            )?;
+        }
+        self.preview = true;
+        Ok(PreviewGuard { camera: self })
+    }
+
+    /// Start showing the preview on the screen, with lens shading correction
+    /// from the given lens shading table file.
+    ///
+    /// See [`LensShadingTable`][crate::LensShadingTable] for the file format.
+    ///
+    /// Returns an error of kind [`Busy`][ErrorKind::Busy] if the preview is already running.
+    pub fn start_preview_fix_lens(
+        &mut self,
+        options: PreviewOptions,
+        lens_table: &Path,
+    ) -> Result<PreviewGuard<'_>> {
+        if self.preview {
+            return Err(Error::new(
+                ErrorKind::Busy,
+                "arducam_start_preview_fix_lens",
+            ));
+        }
+        let path = path_to_cstring(lens_table, "arducam_start_preview_fix_lens")?;
+        let mut params = c::PreviewParams::from(options);
+        unsafe {
+            to_result(
+                c::arducam_start_preview_fix_lens(self.ptr, &mut params, path.as_ptr() as *mut _),
+                "arducam_start_preview_fix_lens",
+            )?;
+        }
+        self.lens_table = Some(path);
+        self.preview = true;
+        Ok(PreviewGuard { camera: self })
+    }
+
+    /// Stop the preview, if it is running.
+    pub(crate) fn stop_preview(&mut self) -> Result<()> {
+        if self.preview {
+            unsafe {
+                to_result(c::arducam_stop_preview(self.ptr), "arducam_stop_preview")?;
+            }
+            self.preview = false;
+        }
+        Ok(())
+    }
+}
+
+impl PreviewGuard<'_> {
+    /// Stop the preview.
+    ///
+    /// Same as dropping the guard, but reports errors.
+    pub fn stop(self) -> Result<()> {
+        let mut this = std::mem::ManuallyDrop::new(self);
+        this.camera.stop_preview()
+    }
+}
+
+impl Deref for PreviewGuard<'_> {
+    type Target = Camera;
+    fn deref(&self) -> &Camera {
+        self.camera
+    }
+}
+
+impl DerefMut for PreviewGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Camera {
+        self.camera
+    }
+}
+
+impl Drop for PreviewGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.camera.stop_preview() {
+            log::error!("failed to stop preview: {}", e);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Raw Bayer frame output.
+
+use crate::{c, BufferFlags, Camera, Format, OutputKind, OutputStream, PixelFormat, Result};
+
+/// A raw Bayer frame, as given to the callback of [`Camera::start_raw_stream`].
+///
+/// A frame is only valid during the callback.
+pub struct RawFrame<'a> {
+    buffer: &'a c::Buffer,
+    format: &'a Format,
+}
+
+impl<'a> RawFrame<'a> {
+    /// The raw image data.
+    pub fn data(&self) -> &'a [u8] {
+        unsafe { self.buffer.bytes() }
+    }
+
+    /// The presentation timestamp of the frame.
+    ///
+    /// Returns `None` when the timestamp is set to `TIME_UNKNOWN`.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.buffer.timestamp()
+    }
+
+    /// The metadata flags of the frame.
+    pub fn flags(&self) -> BufferFlags {
+        BufferFlags::from_bits_retain(self.buffer.flags)
+    }
+
+    /// The buffer ends a frame. If not set, the frame is incomplete.
+    pub fn is_frame_end(&self) -> bool {
+        self.flags().is_frame_end()
+    }
+
+    /// The data is known to be corrupted, or failed to be transmitted.
+    pub fn is_corrupted(&self) -> bool {
+        self.flags().is_corrupted()
+    }
+
+    /// The format of the camera at the time the stream was started.
+    pub fn format(&self) -> &'a Format {
+        self.format
+    }
+
+    /// The width of the frame, in pixels.
+    pub fn width(&self) -> usize {
+        self.format.width as usize
+    }
+
+    /// The height of the frame, in pixels.
+    pub fn height(&self) -> usize {
+        self.format.height as usize
+    }
+
+    /// The pixel format of the frame.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.format.pixel_format()
+    }
+
+    /// The number of bytes between the start of two rows, see [`PixelFormat::stride`].
+    ///
+    /// Returns `None` for unknown formats.
+    pub fn stride(&self) -> Option<usize> {
+        self.pixel_format().stride(self.width())
+    }
+
+    /// Access to the raw [`Buffer`][c::Buffer] structure.
+    pub fn raw_buffer(&self) -> &'a c::Buffer {
+        self.buffer
+    }
+}
+
+impl Camera {
+    /// Start streaming raw Bayer frames, calling `callback` for every frame.
+    ///
+    /// Every frame is tagged with the [`Format`] of the camera at the time this is called.
+    /// The resolution and mode can't be changed while streaming.
+    ///
+    /// The callback is called from another thread.
+    /// Returns an error of kind [`Busy`][crate::ErrorKind::Busy] if raw output is already running.
+    pub fn start_raw_stream<F>(&mut self, mut callback: F) -> Result<OutputStream<'_>>
+    where
+        F: FnMut(RawFrame) + Send + 'static,
+    {
+        let format = self.get_format()?;
+        self.start_output(OutputKind::Raw, None, move |buffer| {
+            callback(RawFrame {
+                buffer,
+                format: &format,
+            })
+        })
+    }
+}
@@ -0,0 +1,171 @@
+//! Direct access to the sensor registers.
+
+use crate::lens::parse_int;
+use crate::{c, to_result, Camera, Error, ErrorKind, Result};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A single register write, optionally followed by a delay.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RegisterWrite {
+    /// The register address.
+    pub address: u16,
+    /// The value to write.
+    pub value: u16,
+    /// Time to wait after writing the register.
+    pub delay: Duration,
+}
+
+impl RegisterWrite {
+    /// A register write without delay.
+    pub fn new(address: u16, value: u16) -> Self {
+        Self {
+            address,
+            value,
+            delay: Duration::from_millis(0),
+        }
+    }
+
+    /// A register write followed by a delay.
+    pub fn with_delay(address: u16, value: u16, delay: Duration) -> Self {
+        Self {
+            address,
+            value,
+            delay,
+        }
+    }
+}
+
+/// A sequence of register writes, such as a sensor mode definition.
+///
+/// The text format has one write per line: the address, the value, and
+/// optionally a delay in milliseconds. Numbers can be decimal or hexadecimal
+/// (`0x`), separated by whitespace or commas. Braces are ignored, and `#` or
+/// `//` start a comment. This means C initializer lists can be used as is:
+///
+/// ```text
+/// # Software reset, then wait 10 ms.
+/// 0x0103 0x01 10
+/// {0x0100, 0x00},
+/// {0x3034, 0x1a}, // 10-bit mode
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegisterTable {
+    /// The writes, in order.
+    pub entries: Vec<RegisterWrite>,
+}
+
+impl RegisterTable {
+    /// Parse a table from the text format.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or("");
+            let line = line.split('#').next().unwrap_or("");
+            let fields: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == ',' || c == '{' || c == '}')
+                .filter(|f| !f.is_empty())
+                .collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                Error::with_message(
+                    ErrorKind::InvalidArgument,
+                    "RegisterTable::parse",
+                    format!("line {}: {}", n + 1, message),
+                )
+            };
+            if fields.len() < 2 || fields.len() > 3 {
+                return Err(invalid("expected address, value, and optional delay"));
+            }
+            let parse_u16 = |s: &str| parse_int(s).and_then(|v| u16::try_from(v).ok());
+            let address = parse_u16(fields[0]).ok_or_else(|| invalid("invalid address"))?;
+            let value = parse_u16(fields[1]).ok_or_else(|| invalid("invalid value"))?;
+            let delay = match fields.get(2) {
+                Some(d) => parse_int(d).ok_or_else(|| invalid("invalid delay"))?,
+                None => 0,
+            };
+            entries.push(RegisterWrite::with_delay(
+                address,
+                value,
+                Duration::from_millis(delay.into()),
+            ));
+        }
+        Ok(Self { entries })
+    }
+
+    /// Read a table from a file in the text format.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            Error::with_message(ErrorKind::Io, "RegisterTable::load", e.to_string())
+        })?;
+        Self::parse(&text)
+    }
+}
+
+impl FromStr for RegisterTable {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for RegisterTable {
+    /// Formats the table in the text format.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            write!(f, "{:#06x} {:#04x}", entry.address, entry.value)?;
+            if entry.delay > Duration::from_millis(0) {
+                write!(f, " {}", entry.delay.as_millis())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Camera {
+    /// Read a sensor register.
+    pub fn read_register(&mut self, address: u16) -> Result<u16> {
+        let mut value = 0;
+        unsafe {
+            to_result(
+                c::arducam_read_sensor_reg(self.ptr, address, &mut value),
+                "arducam_read_sensor_reg",
+            )
+            .map_err(|e| e.context(format!("address {:#06x}", address)))?;
+        }
+        Ok(value)
+    }
+
+    /// Write a sensor register.
+    pub fn write_register(&mut self, address: u16, value: u16) -> Result<()> {
+        unsafe {
+            to_result(
+                c::arducam_write_sensor_reg(self.ptr, address, value),
+                "arducam_write_sensor_reg",
+            )
+            .map_err(|e| e.context(format!("address {:#06x}", address)))
+        }
+    }
+
+    /// Apply a sequence of register writes, waiting after each write as specified.
+    ///
+    /// Stops at the first write that fails.
+    pub fn write_registers<'a>(
+        &mut self,
+        writes: impl IntoIterator<Item = &'a RegisterWrite>,
+    ) -> Result<()> {
+        for write in writes {
+            self.write_register(write.address, write.value)?;
+            if write.delay > Duration::from_millis(0) {
+                std::thread::sleep(write.delay);
+            }
+        }
+        Ok(())
+    }
+}
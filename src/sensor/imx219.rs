@@ -0,0 +1,66 @@
+//! Sony IMX219 (8 MP, Raspberry Pi camera v2).
+
+use super::{Field, Register};
+
+/// Model id, reads [`MODEL_ID_VALUE`].
+pub const MODEL_ID: Register = Register::new("MODEL_ID", 0x0000, 2, 16);
+/// Expected value of [`MODEL_ID`].
+pub const MODEL_ID_VALUE: u32 = 0x0219;
+
+/// Mode select.
+pub const MODE_SELECT: Register = Register::byte("MODE_SELECT", 0x0100);
+/// Streaming (1) or software standby (0).
+pub const STREAMING: Field = MODE_SELECT.field("STREAMING", 0, 1);
+
+/// Software reset.
+pub const SOFTWARE_RESET: Register = Register::byte("SOFTWARE_RESET", 0x0103);
+
+/// Analog gain. The gain is 256 / (256 - value).
+pub const ANALOG_GAIN: Register = Register::byte("ANA_GAIN_GLOBAL_A", 0x0157);
+
+/// Digital gain, in units of 1/256.
+pub const DIGITAL_GAIN: Register = Register::new("DIG_GAIN_GLOBAL_A", 0x0158, 2, 12);
+
+/// Exposure time, in lines.
+pub const COARSE_INTEGRATION_TIME: Register =
+    Register::new("COARSE_INTEGRATION_TIME_A", 0x015A, 2, 16);
+
+/// Frame length, in lines.
+pub const FRAME_LENGTH_LINES: Register = Register::new("FRM_LENGTH_A", 0x0160, 2, 16);
+
+/// Line length, in pixel clocks.
+pub const LINE_LENGTH_PCK: Register = Register::new("LINE_LENGTH_A", 0x0162, 2, 16);
+
+/// Image orientation.
+pub const IMAGE_ORIENTATION: Register = Register::byte("IMG_ORIENTATION_A", 0x0172);
+/// Horizontal mirror.
+pub const HFLIP: Field = IMAGE_ORIENTATION.field("H_MIRROR", 0, 1);
+/// Vertical flip.
+pub const VFLIP: Field = IMAGE_ORIENTATION.field("V_FLIP", 1, 1);
+
+/// Test pattern mode: [`TEST_PATTERN_DISABLED`], [`TEST_PATTERN_SOLID_COLOR`],
+/// [`TEST_PATTERN_COLOR_BARS`], [`TEST_PATTERN_GREY_COLOR_BARS`] or [`TEST_PATTERN_PN9`].
+pub const TEST_PATTERN: Register = Register::new("TEST_PATTERN_MODE", 0x0600, 2, 9);
+/// Red value of the solid color test pattern.
+pub const TEST_DATA_RED: Register = Register::new("TD_R", 0x0602, 2, 10);
+/// Green (in red rows) value of the solid color test pattern.
+pub const TEST_DATA_GREEN_R: Register = Register::new("TD_GR", 0x0604, 2, 10);
+/// Blue value of the solid color test pattern.
+pub const TEST_DATA_BLUE: Register = Register::new("TD_B", 0x0606, 2, 10);
+/// Green (in blue rows) value of the solid color test pattern.
+pub const TEST_DATA_GREEN_B: Register = Register::new("TD_GB", 0x0608, 2, 10);
+/// Width of the test pattern window.
+pub const TEST_PATTERN_WIDTH: Register = Register::new("TP_WINDOW_WIDTH", 0x0624, 2, 12);
+/// Height of the test pattern window.
+pub const TEST_PATTERN_HEIGHT: Register = Register::new("TP_WINDOW_HEIGHT", 0x0626, 2, 12);
+
+/// Value of [`TEST_PATTERN`] to disable the test pattern.
+pub const TEST_PATTERN_DISABLED: u32 = 0;
+/// Value of [`TEST_PATTERN`] for a solid color.
+pub const TEST_PATTERN_SOLID_COLOR: u32 = 1;
+/// Value of [`TEST_PATTERN`] for color bars.
+pub const TEST_PATTERN_COLOR_BARS: u32 = 2;
+/// Value of [`TEST_PATTERN`] for color bars fading to grey.
+pub const TEST_PATTERN_GREY_COLOR_BARS: u32 = 3;
+/// Value of [`TEST_PATTERN`] for a PN9 pseudo-random pattern.
+pub const TEST_PATTERN_PN9: u32 = 4;
@@ -0,0 +1,58 @@
+//! Sony IMX477 (12 MP, Raspberry Pi HQ camera).
+
+use super::{Field, Register};
+
+/// Chip id, reads [`CHIP_ID_VALUE`].
+pub const CHIP_ID: Register = Register::new("CHIP_ID", 0x0016, 2, 16);
+/// Expected value of [`CHIP_ID`].
+pub const CHIP_ID_VALUE: u32 = 0x0477;
+
+/// Mode select.
+pub const MODE_SELECT: Register = Register::byte("MODE_SELECT", 0x0100);
+/// Streaming (1) or software standby (0).
+pub const STREAMING: Field = MODE_SELECT.field("STREAMING", 0, 1);
+
+/// Image orientation.
+pub const IMAGE_ORIENTATION: Register = Register::byte("IMG_ORIENT", 0x0101);
+/// Horizontal mirror.
+pub const HFLIP: Field = IMAGE_ORIENTATION.field("H_MIRROR", 0, 1);
+/// Vertical flip.
+pub const VFLIP: Field = IMAGE_ORIENTATION.field("V_FLIP", 1, 1);
+
+/// Exposure time, in lines.
+pub const COARSE_INTEGRATION_TIME: Register = Register::new("COARSE_INTEG_TIME", 0x0202, 2, 16);
+
+/// Analog gain. The gain is 1024 / (1024 - value).
+pub const ANALOG_GAIN: Register = Register::new("ANA_GAIN_GLOBAL", 0x0204, 2, 10);
+
+/// Digital gain, in units of 1/256.
+pub const DIGITAL_GAIN: Register = Register::new("DIG_GAIN_GLOBAL", 0x020E, 2, 16);
+
+/// Frame length, in lines.
+pub const FRAME_LENGTH_LINES: Register = Register::new("FRM_LENGTH_LINES", 0x0340, 2, 16);
+
+/// Line length, in pixel clocks.
+pub const LINE_LENGTH_PCK: Register = Register::new("LINE_LENGTH_PCK", 0x0342, 2, 16);
+
+/// Test pattern mode: [`TEST_PATTERN_DISABLED`], [`TEST_PATTERN_SOLID_COLOR`],
+/// [`TEST_PATTERN_COLOR_BARS`], [`TEST_PATTERN_GREY_COLOR_BARS`] or [`TEST_PATTERN_PN9`].
+pub const TEST_PATTERN: Register = Register::new("TEST_PATTERN_MODE", 0x0600, 2, 9);
+/// Red value of the solid color test pattern.
+pub const TEST_DATA_RED: Register = Register::new("TEST_DATA_RED", 0x0602, 2, 12);
+/// Green (in red rows) value of the solid color test pattern.
+pub const TEST_DATA_GREEN_R: Register = Register::new("TEST_DATA_GREENR", 0x0604, 2, 12);
+/// Blue value of the solid color test pattern.
+pub const TEST_DATA_BLUE: Register = Register::new("TEST_DATA_BLUE", 0x0606, 2, 12);
+/// Green (in blue rows) value of the solid color test pattern.
+pub const TEST_DATA_GREEN_B: Register = Register::new("TEST_DATA_GREENB", 0x0608, 2, 12);
+
+/// Value of [`TEST_PATTERN`] to disable the test pattern.
+pub const TEST_PATTERN_DISABLED: u32 = 0;
+/// Value of [`TEST_PATTERN`] for a solid color.
+pub const TEST_PATTERN_SOLID_COLOR: u32 = 1;
+/// Value of [`TEST_PATTERN`] for color bars.
+pub const TEST_PATTERN_COLOR_BARS: u32 = 2;
+/// Value of [`TEST_PATTERN`] for color bars fading to grey.
+pub const TEST_PATTERN_GREY_COLOR_BARS: u32 = 3;
+/// Value of [`TEST_PATTERN`] for a PN9 pseudo-random pattern.
+pub const TEST_PATTERN_PN9: u32 = 4;
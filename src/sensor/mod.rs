@@ -0,0 +1,212 @@
+//! Register maps of common sensors.
+//!
+//! These give names to the registers and bitfields of the sensors, for
+//! features that are not available through the [controls][crate::Control].
+//! Writing registers directly bypasses the library, so the library might
+//! overwrite the values again, for example when changing the mode.
+
+use crate::{Camera, Error, ErrorKind, Result};
+
+pub mod imx219;
+pub mod imx477;
+pub mod ov5647;
+pub mod ov9281;
+
+/// A sensor register.
+///
+/// The sensors have 8-bit registers. Values wider than 8 bits span
+/// consecutive addresses, most significant byte first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Register {
+    /// Name of the register, as used in the datasheet.
+    pub name: &'static str,
+    /// Address of the (first) register.
+    pub address: u16,
+    /// Number of consecutive 8-bit registers.
+    pub bytes: u8,
+    /// Number of bits of the value.
+    pub bits: u8,
+}
+
+/// A bitfield within a [`Register`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// Name of the field.
+    pub name: &'static str,
+    /// The register containing the field.
+    pub register: Register,
+    /// Position of the least significant bit of the field.
+    pub shift: u8,
+    /// Number of bits of the field.
+    pub width: u8,
+}
+
+/// One of the supported sensors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sensor {
+    /// OmniVision OV5647, see [`ov5647`].
+    Ov5647,
+    /// Sony IMX219, see [`imx219`].
+    Imx219,
+    /// Sony IMX477, see [`imx477`].
+    Imx477,
+    /// OmniVision OV9281, see [`ov9281`].
+    Ov9281,
+}
+
+impl Register {
+    /// A register of `bytes` consecutive addresses, holding a value of `bits` bits.
+    pub const fn new(name: &'static str, address: u16, bytes: u8, bits: u8) -> Self {
+        Self {
+            name,
+            address,
+            bytes,
+            bits,
+        }
+    }
+
+    /// A single 8-bit register.
+    pub const fn byte(name: &'static str, address: u16) -> Self {
+        Self::new(name, address, 1, 8)
+    }
+
+    /// A bitfield of this register.
+    pub const fn field(self, name: &'static str, shift: u8, width: u8) -> Field {
+        Field {
+            name,
+            register: self,
+            shift,
+            width,
+        }
+    }
+
+    /// The largest value that fits in the register.
+    pub fn max_value(&self) -> u32 {
+        mask(self.bits)
+    }
+
+    /// Read the value of the register.
+    pub fn read(&self, camera: &mut Camera) -> Result<u32> {
+        let mut value = 0u32;
+        for i in 0..self.bytes {
+            let byte = camera.read_register(self.address + u16::from(i))?;
+            value = value << 8 | u32::from(byte & 0xFF);
+        }
+        Ok(value & self.max_value())
+    }
+
+    /// Write a value to the register.
+    ///
+    /// Returns an error of kind [`InvalidArgument`][ErrorKind::InvalidArgument]
+    /// if the value does not fit.
+    pub fn write(&self, camera: &mut Camera, value: u32) -> Result<()> {
+        if value > self.max_value() {
+            return Err(Error::with_message(
+                ErrorKind::InvalidArgument,
+                "Register::write",
+                format!(
+                    "value {:#x} does not fit in {} ({} bits)",
+                    value, self.name, self.bits
+                ),
+            ));
+        }
+        for i in 0..self.bytes {
+            let byte = value >> (8 * (self.bytes - 1 - i)) & 0xFF;
+            camera.write_register(self.address + u16::from(i), byte as u16)?;
+        }
+        Ok(())
+    }
+}
+
+impl Field {
+    /// The largest value that fits in the field.
+    pub fn max_value(&self) -> u32 {
+        mask(self.width)
+    }
+
+    /// Read the value of the field.
+    pub fn read(&self, camera: &mut Camera) -> Result<u32> {
+        Ok(self.register.read(camera)? >> self.shift & self.max_value())
+    }
+
+    /// Write a value to the field, leaving the other bits of the register unchanged.
+    ///
+    /// Returns an error of kind [`InvalidArgument`][ErrorKind::InvalidArgument]
+    /// if the value does not fit.
+    pub fn write(&self, camera: &mut Camera, value: u32) -> Result<()> {
+        if value > self.max_value() {
+            return Err(Error::with_message(
+                ErrorKind::InvalidArgument,
+                "Field::write",
+                format!(
+                    "value {:#x} does not fit in {}.{} ({} bits)",
+                    value, self.register.name, self.name, self.width
+                ),
+            ));
+        }
+        let mask = self.max_value() << self.shift;
+        let old = self.register.read(camera)?;
+        self.register
+            .write(camera, old & !mask | value << self.shift)
+    }
+
+    /// Read a single bit field as a boolean.
+    pub fn is_set(&self, camera: &mut Camera) -> Result<bool> {
+        Ok(self.read(camera)? != 0)
+    }
+
+    /// Write a single bit field as a boolean.
+    pub fn set(&self, camera: &mut Camera, value: bool) -> Result<()> {
+        self.write(camera, value as u32)
+    }
+}
+
+impl Sensor {
+    /// The register holding the chip id of this sensor.
+    pub fn chip_id_register(self) -> Register {
+        match self {
+            Sensor::Ov5647 => ov5647::CHIP_ID,
+            Sensor::Imx219 => imx219::MODEL_ID,
+            Sensor::Imx477 => imx477::CHIP_ID,
+            Sensor::Ov9281 => ov9281::CHIP_ID,
+        }
+    }
+
+    /// The expected value of the chip id register.
+    pub fn chip_id(self) -> u32 {
+        match self {
+            Sensor::Ov5647 => ov5647::CHIP_ID_VALUE,
+            Sensor::Imx219 => imx219::MODEL_ID_VALUE,
+            Sensor::Imx477 => imx477::CHIP_ID_VALUE,
+            Sensor::Ov9281 => ov9281::CHIP_ID_VALUE,
+        }
+    }
+
+    /// Find out which of the supported sensors is connected, by reading the chip id registers.
+    ///
+    /// Returns `None` if it is none of the supported sensors.
+    pub fn detect(camera: &mut Camera) -> Option<Sensor> {
+        for &sensor in &[
+            Sensor::Ov5647,
+            Sensor::Imx219,
+            Sensor::Imx477,
+            Sensor::Ov9281,
+        ] {
+            // Reading a register that does not exist on the connected sensor can fail.
+            if let Ok(id) = sensor.chip_id_register().read(camera) {
+                if id == sensor.chip_id() {
+                    return Some(sensor);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn mask(bits: u8) -> u32 {
+    if bits >= 32 {
+        u32::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
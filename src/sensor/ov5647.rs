@@ -0,0 +1,57 @@
+//! OmniVision OV5647 (5 MP, Raspberry Pi camera v1).
+
+use super::{Field, Register};
+
+/// Chip id, reads [`CHIP_ID_VALUE`].
+pub const CHIP_ID: Register = Register::new("CHIP_ID", 0x300A, 2, 16);
+/// Expected value of [`CHIP_ID`].
+pub const CHIP_ID_VALUE: u32 = 0x5647;
+
+/// Mode select.
+pub const MODE_SELECT: Register = Register::byte("MODE_SELECT", 0x0100);
+/// Streaming (1) or software standby (0).
+pub const STREAMING: Field = MODE_SELECT.field("STREAMING", 0, 1);
+
+/// Software reset.
+pub const SOFTWARE_RESET: Register = Register::byte("SOFTWARE_RESET", 0x0103);
+
+/// Exposure time, in units of 1/16 line.
+pub const EXPOSURE: Register = Register::new("AEC_EXPO", 0x3500, 3, 20);
+
+/// Analog gain, in units of 1/16.
+pub const ANALOG_GAIN: Register = Register::new("AEC_AGC_ADJ", 0x350A, 2, 10);
+
+/// Horizontal total size (line length), in pixel clocks.
+pub const LINE_LENGTH_PCK: Register = Register::new("TIMING_HTS", 0x380C, 2, 13);
+
+/// Vertical total size (frame length), in lines.
+pub const FRAME_LENGTH_LINES: Register = Register::new("TIMING_VTS", 0x380E, 2, 16);
+
+/// Timing control for vertical flip.
+pub const TIMING_TC_REG20: Register = Register::byte("TIMING_TC_REG20", 0x3820);
+/// Vertical flip.
+pub const VFLIP: Field = TIMING_TC_REG20.field("VFLIP", 1, 1);
+
+/// Timing control for horizontal mirror.
+pub const TIMING_TC_REG21: Register = Register::byte("TIMING_TC_REG21", 0x3821);
+/// Horizontal mirror.
+pub const HFLIP: Field = TIMING_TC_REG21.field("MIRROR", 1, 1);
+
+/// Test pattern control.
+pub const TEST_PATTERN: Register = Register::byte("PRE_ISP_TEST_SETTING_1", 0x503D);
+/// Test pattern enable.
+pub const TEST_PATTERN_ENABLE: Field = TEST_PATTERN.field("TEST_EN", 7, 1);
+/// Test pattern select: [`TEST_PATTERN_COLOR_BAR`], [`TEST_PATTERN_RANDOM`],
+/// [`TEST_PATTERN_SQUARE`] or [`TEST_PATTERN_BLACK`].
+pub const TEST_PATTERN_SELECT: Field = TEST_PATTERN.field("TEST_SELECT", 0, 2);
+/// Color bar style, when the color bar test pattern is selected.
+pub const TEST_PATTERN_BAR_STYLE: Field = TEST_PATTERN.field("BAR_STYLE", 2, 2);
+
+/// Value of [`TEST_PATTERN_SELECT`] for color bars.
+pub const TEST_PATTERN_COLOR_BAR: u32 = 0;
+/// Value of [`TEST_PATTERN_SELECT`] for random data.
+pub const TEST_PATTERN_RANDOM: u32 = 1;
+/// Value of [`TEST_PATTERN_SELECT`] for squares.
+pub const TEST_PATTERN_SQUARE: u32 = 2;
+/// Value of [`TEST_PATTERN_SELECT`] for a black image.
+pub const TEST_PATTERN_BLACK: u32 = 3;
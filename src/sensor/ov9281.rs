@@ -0,0 +1,45 @@
+//! OmniVision OV9281 (1 MP, global shutter, monochrome).
+
+use super::{Field, Register};
+
+/// Chip id, reads [`CHIP_ID_VALUE`].
+pub const CHIP_ID: Register = Register::new("CHIP_ID", 0x300A, 2, 16);
+/// Expected value of [`CHIP_ID`].
+pub const CHIP_ID_VALUE: u32 = 0x9281;
+
+/// Mode select.
+pub const MODE_SELECT: Register = Register::byte("MODE_SELECT", 0x0100);
+/// Streaming (1) or software standby (0).
+pub const STREAMING: Field = MODE_SELECT.field("STREAMING", 0, 1);
+
+/// Software reset.
+pub const SOFTWARE_RESET: Register = Register::byte("SOFTWARE_RESET", 0x0103);
+
+/// Exposure time, in units of 1/16 line.
+pub const EXPOSURE: Register = Register::new("EXPO", 0x3500, 3, 20);
+
+/// Analog gain, in units of 1/16.
+pub const ANALOG_GAIN: Register = Register::byte("GAIN", 0x3509);
+
+/// Horizontal total size (line length), in pixel clocks.
+pub const LINE_LENGTH_PCK: Register = Register::new("TIMING_HTS", 0x380C, 2, 16);
+
+/// Vertical total size (frame length), in lines.
+pub const FRAME_LENGTH_LINES: Register = Register::new("TIMING_VTS", 0x380E, 2, 16);
+
+/// Timing format 1.
+pub const TIMING_FORMAT1: Register = Register::byte("TIMING_FORMAT1", 0x3820);
+/// Vertical flip.
+pub const VFLIP: Field = TIMING_FORMAT1.field("VFLIP", 2, 1);
+
+/// Timing format 2.
+pub const TIMING_FORMAT2: Register = Register::byte("TIMING_FORMAT2", 0x3821);
+/// Horizontal mirror.
+pub const HFLIP: Field = TIMING_FORMAT2.field("MIRROR", 2, 1);
+
+/// Test pattern control.
+pub const TEST_PATTERN: Register = Register::byte("PRE_CTRL00", 0x5E00);
+/// Test pattern enable.
+pub const TEST_PATTERN_ENABLE: Field = TEST_PATTERN.field("TEST_PATTERN_EN", 7, 1);
+/// Color bar style.
+pub const TEST_PATTERN_BAR_STYLE: Field = TEST_PATTERN.field("BAR_STYLE", 2, 2);
@@ -0,0 +1,263 @@
+//! A camera handle that can be shared between threads.
+//!
+//! The ArduCAM library does not document whether it is thread safe. It can
+//! be used from any thread, but calls for the same camera must not overlap.
+//! [`Camera`] is therefore [`Send`] but not [`Sync`], and [`SharedCamera`]
+//! serializes all calls through a mutex.
+//!
+//! Output callbacks are called from a thread of the library, and don't lock
+//! the camera. [`SharedFrames`] only locks it to start and stop the output, so
+//! one thread can receive frames while another one adjusts the controls.
+//!
+//! Dropping a [`SharedFrames`] while the same thread holds the camera through
+//! [`SharedCamera::lock`] can't stop the output right away. The output is then
+//! stopped when that lock is released.
+
+use crate::frames::Queue;
+use crate::{
+    c, Camera, CameraInterface, Control, Format, Frame, OutputKind, OverflowPolicy, Result,
+    VideoEncoderConfig,
+};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::ThreadId;
+use std::time::Duration;
+
+/// A handle to a [`Camera`] that can be cloned and used from multiple threads.
+///
+/// Every call locks the camera for its duration. Use [`lock`][SharedCamera::lock]
+/// for access to the full [`Camera`] interface, or to make several calls without
+/// other threads getting in between.
+///
+/// The camera is closed when the last handle and [`SharedFrames`] are dropped.
+#[derive(Clone)]
+pub struct SharedCamera {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    camera: Mutex<Camera>,
+    lock_state: Mutex<LockState>,
+}
+
+#[derive(Default)]
+struct LockState {
+    /// The thread holding the camera, if any.
+    owner: Option<ThreadId>,
+    /// Outputs of [`SharedFrames`] dropped by the owner, to stop when it releases the camera.
+    pending_stops: Vec<OutputKind>,
+}
+
+/// The camera locked by [`SharedCamera::lock`].
+///
+/// Dereferences to the [`Camera`]. Unlocks the camera when dropped.
+pub struct SharedCameraGuard<'a> {
+    camera: MutexGuard<'a, Camera>,
+    lock_state: &'a Mutex<LockState>,
+}
+
+/// Iterator over the frames of an output, returned by [`SharedCamera::frames`].
+///
+/// Same as [`Frames`][crate::Frames], except that it does not lock the camera
+/// while waiting for frames. Stops the output when dropped, which waits for the
+/// camera if another thread has it locked. If the dropping thread has it
+/// locked, the output is stopped when that lock is released.
+pub struct SharedFrames {
+    pub(crate) queue: Arc<Queue>,
+    timeout: Option<Duration>,
+    camera: SharedCamera,
+    kind: OutputKind,
+}
+
+impl SharedCamera {
+    /// Share a camera.
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                camera: Mutex::new(camera),
+                lock_state: Mutex::new(LockState::default()),
+            }),
+        }
+    }
+
+    /// Initialize a camera, see [`Camera::init`].
+    pub fn init(interface: Option<CameraInterface>) -> Result<Self> {
+        Camera::init(interface).map(Self::new)
+    }
+
+    /// Lock the camera, blocking until no other thread is using it.
+    ///
+    /// [`SharedFrames`] dropped by this thread while the returned guard is held
+    /// keep their output running until the guard is dropped.
+    pub fn lock(&self) -> SharedCameraGuard<'_> {
+        let camera = self.shared.camera.lock().unwrap_or_else(|e| e.into_inner());
+        lock(&self.shared.lock_state).owner = Some(std::thread::current().id());
+        SharedCameraGuard {
+            camera,
+            lock_state: &self.shared.lock_state,
+        }
+    }
+
+    /// Get the current output format, see [`Camera::get_format`].
+    pub fn get_format(&self) -> Result<Format> {
+        self.lock().get_format()
+    }
+
+    /// Set a camera control to default value, see [`Camera::reset_control`].
+    pub fn reset_control(&self, control: impl Into<Control>) -> Result<()> {
+        self.lock().reset_control(control)
+    }
+
+    /// Set a camera control to the specified value, see [`Camera::set_control`].
+    pub fn set_control(&self, control: impl Into<Control>, value: i32) -> Result<()> {
+        self.lock().set_control(control, value)
+    }
+
+    /// Read the current value of a camera control, see [`Camera::get_control`].
+    pub fn get_control(&self, control: impl Into<Control>) -> Result<i32> {
+        self.lock().get_control(control)
+    }
+
+    /// Start the given output, and return an iterator over its frames.
+    ///
+    /// See [`Camera::frames`].
+    pub fn frames(&self, kind: OutputKind, capacity: usize) -> Result<SharedFrames> {
+        let encoder_state = match kind {
+            OutputKind::Video => Some(VideoEncoderConfig::default().into()),
+            _ => None,
+        };
+        self.start_frames(kind, encoder_state, capacity)
+    }
+
+    /// Start encoding video, and return an iterator over the encoded data.
+    ///
+    /// See [`Camera::video_frames`].
+    pub fn video_frames(
+        &self,
+        config: VideoEncoderConfig,
+        capacity: usize,
+    ) -> Result<SharedFrames> {
+        self.start_frames(OutputKind::Video, Some(config.into()), capacity)
+    }
+
+    fn start_frames(
+        &self,
+        kind: OutputKind,
+        encoder_state: Option<c::VideoEncoderState>,
+        capacity: usize,
+    ) -> Result<SharedFrames> {
+        let queue = Queue::new(capacity)?;
+        {
+            let mut camera = self.lock();
+            let format = camera.get_format()?;
+            camera.register_output(kind, encoder_state, queue.producer(kind, format))?;
+        }
+        Ok(SharedFrames {
+            queue,
+            timeout: None,
+            camera: self.clone(),
+            kind,
+        })
+    }
+}
+
+impl SharedFrames {
+    /// The kind of output.
+    pub fn kind(&self) -> OutputKind {
+        self.kind
+    }
+
+    /// The camera the frames come from.
+    pub fn camera(&self) -> &SharedCamera {
+        &self.camera
+    }
+
+    /// Set what to do with new frames when the queue is full.
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.queue.set_overflow_policy(policy);
+    }
+
+    /// Set how long [`next`][Iterator::next] waits for a frame before returning a timeout error.
+    ///
+    /// By default (`None`), it waits forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// The total number of frames dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// The number of frames currently in the queue.
+    pub fn queued(&self) -> usize {
+        self.queue.queued()
+    }
+
+    /// Stop the output.
+    ///
+    /// Same as dropping the iterator, but reports errors.
+    pub fn stop(self) -> Result<()> {
+        self.queue.close();
+        self.camera.lock().stop_output(self.kind)
+    }
+}
+
+impl Iterator for SharedFrames {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Result<Frame>> {
+        Some(self.queue.pop(self.timeout, "SharedFrames::next"))
+    }
+}
+
+impl Drop for SharedFrames {
+    fn drop(&mut self) {
+        // Unblock the callback thread before the output is stopped.
+        self.queue.close();
+        {
+            // Locking the camera would deadlock if this thread has it locked.
+            let mut state = lock(&self.camera.shared.lock_state);
+            if state.owner == Some(std::thread::current().id()) {
+                state.pending_stops.push(self.kind);
+                return;
+            }
+        }
+        if let Err(e) = self.camera.lock().stop_output(self.kind) {
+            log::error!("failed to stop {:?} output: {}", self.kind, e);
+        }
+    }
+}
+
+impl Deref for SharedCameraGuard<'_> {
+    type Target = Camera;
+
+    fn deref(&self) -> &Camera {
+        &self.camera
+    }
+}
+
+impl DerefMut for SharedCameraGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+}
+
+impl Drop for SharedCameraGuard<'_> {
+    fn drop(&mut self) {
+        let pending = {
+            let mut state = lock(self.lock_state);
+            state.owner = None;
+            std::mem::take(&mut state.pending_stops)
+        };
+        for kind in pending {
+            if let Err(e) = self.camera.stop_output(kind) {
+                log::error!("failed to stop {:?} output: {}", kind, e);
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,692 @@
+//! Rust implementations of the C interface, used in place of the library.
+//!
+//! These have the same signatures as the functions in the `extern` block in
+//! [`c`][crate::c], which re-exports them when the `simulator` feature is enabled.
+
+#![allow(clippy::missing_safety_doc)]
+
+use super::{config, failure, image, Compiled};
+use crate::c::{
+    Buffer, CameraCtrl, CameraInstance, CameraInterface, Format, ImageFormat, OutputCallback,
+    PreviewParams, VideoEncoderState,
+};
+use crate::sensor::{imx219, Sensor};
+use crate::unpack::{raw10_stride, unpack_raw10_to_raw16, unpack_raw10_to_raw8};
+use crate::{c, PixelFormat, TestPattern};
+use std::collections::HashMap;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const EINVAL: c_int = -22;
+const ERANGE: c_int = -34;
+
+/// A simulated camera, behind a [`CameraInstance`] pointer.
+struct SimCamera {
+    config: Arc<Compiled>,
+    state: Mutex<State>,
+    /// Held while calling the callbacks, so they are not called after they are unregistered.
+    outputs: Mutex<[Option<Output>; 3]>,
+    closed: AtomicBool,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct State {
+    mode: usize,
+    width: i32,
+    height: i32,
+    controls: HashMap<i32, i32>,
+    registers: HashMap<u16, u16>,
+    frame: u64,
+}
+
+#[derive(Copy, Clone)]
+struct Output {
+    callback: OutputCallback,
+    /// The userdata pointer, as integer to make it `Send`.
+    userdata: usize,
+    encoding: u32,
+    intra_period: u64,
+    /// Whether the codec config (SPS/PPS) has been sent already.
+    config_sent: bool,
+}
+
+/// A buffer allocated by the simulator, released by [`arducam_release_buffer`].
+#[repr(C)]
+struct SimBuffer {
+    header: Buffer,
+    data: Vec<u8>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn new_buffer(mut data: Vec<u8>, flags: u32, pts: i64) -> *mut Buffer {
+    let header = Buffer {
+        private: std::ptr::null_mut(),
+        data: data.as_mut_ptr(),
+        alloc_size: data.capacity() as u32,
+        length: data.len() as u32,
+        flags,
+        pts,
+        userdata: std::ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(SimBuffer { header, data })) as *mut Buffer
+}
+
+unsafe fn camera<'a>(instance: CameraInstance) -> Option<&'a SimCamera> {
+    (instance as *const SimCamera).as_ref()
+}
+
+/// Return early with the injected error code, or with `EINVAL` if the instance is null.
+macro_rules! enter {
+    ($function:literal, $instance:expr) => {{
+        if let Some(code) = failure($function) {
+            return code;
+        }
+        match camera($instance) {
+            Some(camera) => camera,
+            None => return EINVAL,
+        }
+    }};
+}
+
+impl SimCamera {
+    fn new() -> Self {
+        let config = config();
+        let mut registers = HashMap::new();
+        if let Some(sensor) = config.sensor {
+            let register = sensor.chip_id_register();
+            let id = sensor.chip_id();
+            for i in 0..register.bytes {
+                let byte = id >> (8 * (register.bytes - 1 - i)) & 0xFF;
+                registers.insert(register.address + u16::from(i), byte as u16);
+            }
+        }
+        let (width, height) = config
+            .modes
+            .first()
+            .map_or((0, 0), |(m, _)| (m.width, m.height));
+        let controls = config
+            .controls
+            .iter()
+            .map(|(c, _)| (c.control.id(), c.default))
+            .collect();
+        Self {
+            config,
+            state: Mutex::new(State {
+                mode: 0,
+                width,
+                height,
+                controls,
+                registers,
+                frame: 0,
+            }),
+            outputs: Mutex::new([None; 3]),
+            closed: AtomicBool::new(false),
+            thread: Mutex::new(None),
+        }
+    }
+
+    /// The current format, or `None` if the sensor has no modes.
+    fn format(&self, state: &State) -> Option<Format> {
+        let modes = &self.config.modes;
+        let (mode, desc) = modes.get(state.mode).or_else(|| modes.last())?;
+        Some(Format {
+            mode: state.mode as c_int,
+            width: state.width,
+            height: state.height,
+            pixelformat: mode.pixel_format.fourcc(),
+            frameintervals: mode.frame_interval,
+            description: desc.as_ptr(),
+            reserved: [0; 4],
+        })
+    }
+
+    /// The image the sensor currently produces.
+    fn pattern(&self, state: &State) -> TestPattern {
+        let register =
+            |address: u16| u32::from(state.registers.get(&address).copied().unwrap_or(0));
+        let value = |r: crate::sensor::Register| {
+            (0..r.bytes).fold(0, |v, i| v << 8 | register(r.address + u16::from(i)))
+        };
+        match self.config.sensor {
+            Some(Sensor::Imx219) | Some(Sensor::Imx477) => {
+                match value(imx219::TEST_PATTERN) {
+                    imx219::TEST_PATTERN_SOLID_COLOR => {
+                        // The test data registers have 10 (IMX219) or 12 (IMX477) bits.
+                        let shift = if self.config.sensor == Some(Sensor::Imx219) {
+                            2
+                        } else {
+                            4
+                        };
+                        let c = |r| (value(r) >> shift).min(255) as u8;
+                        TestPattern::SolidColor {
+                            red: c(imx219::TEST_DATA_RED),
+                            green: c(imx219::TEST_DATA_GREEN_R),
+                            blue: c(imx219::TEST_DATA_BLUE),
+                        }
+                    }
+                    imx219::TEST_PATTERN_GREY_COLOR_BARS => TestPattern::Gradient,
+                    _ => TestPattern::ColorBars,
+                }
+            }
+            _ => TestPattern::ColorBars,
+        }
+    }
+
+    /// The time between frames in the current mode.
+    fn frame_interval(&self) -> Duration {
+        let state = lock(&self.state);
+        match self.format(&state).map(|format| format.frameintervals) {
+            Some(interval) if interval.denominator != 0 && interval.numerator != 0 => {
+                Duration::from_secs(u64::from(interval.numerator)) / interval.denominator
+            }
+            _ => Duration::from_millis(33),
+        }
+    }
+
+    /// Produce frames for the outputs until the camera is closed.
+    fn run(&self) {
+        while !self.closed.load(Ordering::Acquire) {
+            let interval = self.frame_interval();
+            std::thread::sleep(interval);
+            let mut outputs = lock(&self.outputs);
+            if outputs.iter().all(Option::is_none) {
+                continue;
+            }
+            let (format, pattern, frame) = {
+                let mut state = lock(&self.state);
+                // Without a mode there is nothing to produce.
+                let format = match self.format(&state) {
+                    Some(format) => format,
+                    None => continue,
+                };
+                state.frame += 1;
+                (format, self.pattern(&state), state.frame)
+            };
+            let pts = (interval * frame as u32).as_micros() as i64;
+            let (width, height) = (format.width as usize, format.height as usize);
+            for (index, output) in outputs.iter_mut().enumerate() {
+                let output = match output {
+                    Some(output) => output,
+                    None => continue,
+                };
+                let mut chunks = Vec::new();
+                match index {
+                    0 => {
+                        if output.encoding == c::VIDEO_ENCODING_MJPEG {
+                            let flags = c::MMAL_BUFFER_HEADER_FLAG_FRAME_END
+                                | c::MMAL_BUFFER_HEADER_FLAG_KEYFRAME;
+                            chunks.push((image::jpeg(pattern, width, height, 75), flags));
+                        } else {
+                            if !output.config_sent {
+                                output.config_sent = true;
+                                let sps_pps =
+                                    vec![0, 0, 0, 1, 0x67, 0x64, 0, 0x28, 0, 0, 0, 1, 0x68];
+                                chunks.push((sps_pps, c::MMAL_BUFFER_HEADER_FLAG_CONFIG));
+                            }
+                            let keyframe = (frame - 1) % output.intra_period.max(1) == 0;
+                            let mut data = vec![0, 0, 0, 1, if keyframe { 0x65 } else { 0x41 }];
+                            data.extend_from_slice(&frame.to_be_bytes());
+                            let mut flags = c::MMAL_BUFFER_HEADER_FLAG_FRAME_END;
+                            if keyframe {
+                                flags |= c::MMAL_BUFFER_HEADER_FLAG_KEYFRAME;
+                            }
+                            chunks.push((data, flags));
+                        }
+                    }
+                    1 => {
+                        let pixel_format = PixelFormat::from_fourcc(format.pixelformat);
+                        let data = image::raw(pattern, pixel_format, width, height);
+                        chunks.push((data, c::MMAL_BUFFER_HEADER_FLAG_FRAME_END));
+                    }
+                    _ => {
+                        let data = image::i420(pattern, width, height);
+                        chunks.push((data, c::MMAL_BUFFER_HEADER_FLAG_FRAME_END));
+                    }
+                }
+                for (mut data, flags) in chunks {
+                    let mut buffer = Buffer {
+                        private: std::ptr::null_mut(),
+                        data: data.as_mut_ptr(),
+                        alloc_size: data.capacity() as u32,
+                        length: data.len() as u32,
+                        flags,
+                        pts,
+                        userdata: output.userdata as *mut c_void,
+                    };
+                    unsafe { (output.callback)(&mut buffer) };
+                }
+            }
+        }
+    }
+
+    fn set_output(&self, index: usize, output: Option<Output>) -> c_int {
+        lock(&self.outputs)[index] = output;
+        0
+    }
+}
+
+pub unsafe extern "C" fn arducam_init_camera(camera_instance: *mut CameraInstance) -> c_int {
+    if let Some(code) = failure("arducam_init_camera") {
+        return code;
+    }
+    if camera_instance.is_null() {
+        return EINVAL;
+    }
+    let camera = Arc::new(SimCamera::new());
+    let runner = camera.clone();
+    let thread = std::thread::Builder::new()
+        .name("simulated camera".into())
+        .spawn(move || runner.run());
+    match thread {
+        Ok(thread) => *lock(&camera.thread) = Some(thread),
+        Err(_) => return -12,
+    }
+    *camera_instance = Arc::into_raw(camera) as CameraInstance;
+    0
+}
+
+pub unsafe extern "C" fn arducam_init_camera2(
+    camera_instance: *mut CameraInstance,
+    _cam_interface: CameraInterface,
+) -> c_int {
+    if let Some(code) = failure("arducam_init_camera2") {
+        return code;
+    }
+    arducam_init_camera(camera_instance)
+}
+
+pub unsafe extern "C" fn arducam_set_resolution(
+    camera_instance: CameraInstance,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> c_int {
+    let camera = enter!("arducam_set_resolution", camera_instance);
+    if width.is_null() || height.is_null() || *width <= 0 || *height <= 0 {
+        return EINVAL;
+    }
+    let (w, h) = (*width, *height);
+    // The smallest mode that covers the resolution, or the largest mode.
+    let modes = &camera.config.modes;
+    let area = |i: usize| i64::from(modes[i].0.width) * i64::from(modes[i].0.height);
+    let mode = (0..modes.len())
+        .filter(|&i| modes[i].0.width >= w && modes[i].0.height >= h)
+        .min_by_key(|&i| area(i))
+        .or_else(|| (0..modes.len()).max_by_key(|&i| area(i)));
+    let mut state = lock(&camera.state);
+    state.mode = mode.unwrap_or(0);
+    state.width = w;
+    state.height = h;
+    0
+}
+
+pub unsafe extern "C" fn arducam_set_mode(camera_instance: CameraInstance, mode: c_int) -> c_int {
+    let camera = enter!("arducam_set_mode", camera_instance);
+    match camera.config.modes.get(mode as usize) {
+        Some((m, _)) if mode >= 0 => {
+            let mut state = lock(&camera.state);
+            state.mode = mode as usize;
+            state.width = m.width;
+            state.height = m.height;
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_set_lens_table(
+    camera_instance: CameraInstance,
+    lens_table_path: *mut c_char,
+) -> c_int {
+    enter!("arducam_set_lens_table", camera_instance);
+    if lens_table_path.is_null() {
+        return EINVAL;
+    }
+    0
+}
+
+pub unsafe extern "C" fn arducam_get_format(
+    camera_instance: CameraInstance,
+    fmt: *mut Format,
+) -> c_int {
+    let camera = enter!("arducam_get_format", camera_instance);
+    match (fmt.as_mut(), camera.format(&lock(&camera.state))) {
+        (Some(fmt), Some(format)) => {
+            *fmt = format;
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_set_video_callback(
+    camera_instance: CameraInstance,
+    encoder_state: *mut VideoEncoderState,
+    callback: Option<OutputCallback>,
+    userdata: *mut c_void,
+) -> c_int {
+    let camera = enter!("arducam_set_video_callback", camera_instance);
+    let state = encoder_state.as_ref();
+    let output = callback.map(|callback| Output {
+        callback,
+        userdata: userdata as usize,
+        encoding: state.map_or(c::VIDEO_ENCODING_H264, |s| s.encoding),
+        intra_period: state.map_or(30, |s| s.intraperiod.max(1) as u64),
+        config_sent: false,
+    });
+    camera.set_output(0, output)
+}
+
+pub unsafe extern "C" fn arducam_set_raw_callback(
+    camera_instance: CameraInstance,
+    callback: Option<OutputCallback>,
+    userdata: *mut c_void,
+) -> c_int {
+    let camera = enter!("arducam_set_raw_callback", camera_instance);
+    let output = callback.map(|callback| Output {
+        callback,
+        userdata: userdata as usize,
+        encoding: 0,
+        intra_period: 1,
+        config_sent: false,
+    });
+    camera.set_output(1, output)
+}
+
+pub unsafe extern "C" fn arducam_set_yuv_callback(
+    camera_instance: CameraInstance,
+    callback: Option<OutputCallback>,
+    userdata: *mut c_void,
+) -> c_int {
+    let camera = enter!("arducam_set_yuv_callback", camera_instance);
+    let output = callback.map(|callback| Output {
+        callback,
+        userdata: userdata as usize,
+        encoding: 0,
+        intra_period: 1,
+        config_sent: false,
+    });
+    camera.set_output(2, output)
+}
+
+pub unsafe extern "C" fn arducam_capture(
+    camera_instance: CameraInstance,
+    format: *mut ImageFormat,
+    _timeout: c_int,
+) -> *mut Buffer {
+    // Any failure is reported as a timeout.
+    if failure("arducam_capture").is_some() {
+        return std::ptr::null_mut();
+    }
+    let (camera, format) = match (camera(camera_instance), format.as_ref()) {
+        (Some(camera), Some(format)) => (camera, format),
+        _ => return std::ptr::null_mut(),
+    };
+    let (current, pattern) = {
+        let state = lock(&camera.state);
+        match camera.format(&state) {
+            Some(current) => (current, camera.pattern(&state)),
+            None => return std::ptr::null_mut(),
+        }
+    };
+    let (width, height) = (current.width as usize, current.height as usize);
+    let data = match format.encoding {
+        c::IMAGE_ENCODING_I420 => image::i420(pattern, width, height),
+        c::IMAGE_ENCODING_JPEG => image::jpeg(pattern, width, height, format.quality),
+        c::IMAGE_ENCODING_RAW_BAYER => {
+            let pixel_format = PixelFormat::from_fourcc(current.pixelformat);
+            image::raw(pattern, pixel_format, width, height)
+        }
+        c::IMAGE_ENCODING_BMP => image::bmp(pattern, width, height),
+        c::IMAGE_ENCODING_PNG => image::png(pattern, width, height),
+        _ => return std::ptr::null_mut(),
+    };
+    let flags = c::MMAL_BUFFER_HEADER_FLAG_FRAME_END;
+    new_buffer(data, flags, c::TIME_UNKNOWN)
+}
+
+pub unsafe extern "C" fn arducam_release_buffer(buffer: *mut Buffer) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(buffer as *mut SimBuffer));
+    }
+}
+
+pub unsafe extern "C" fn arducam_start_preview(
+    camera_instance: CameraInstance,
+    _preview_params: *mut PreviewParams,
+) -> c_int {
+    enter!("arducam_start_preview", camera_instance);
+    0
+}
+
+pub unsafe extern "C" fn arducam_start_preview_fix_lens(
+    camera_instance: CameraInstance,
+    _preview_params: *mut PreviewParams,
+    lens_table_path: *mut c_char,
+) -> c_int {
+    enter!("arducam_start_preview_fix_lens", camera_instance);
+    if lens_table_path.is_null() {
+        return EINVAL;
+    }
+    0
+}
+
+pub unsafe extern "C" fn arducam_stop_preview(camera_instance: CameraInstance) -> c_int {
+    enter!("arducam_stop_preview", camera_instance);
+    0
+}
+
+pub unsafe extern "C" fn arducam_close_camera(camera_instance: CameraInstance) -> c_int {
+    enter!("arducam_close_camera", camera_instance);
+    let camera = Arc::from_raw(camera_instance as *const SimCamera);
+    camera.closed.store(true, Ordering::Release);
+    let thread = lock(&camera.thread).take();
+    if let Some(thread) = thread {
+        let _ = thread.join();
+    }
+    0
+}
+
+pub unsafe extern "C" fn arducam_reset_control(
+    camera_instance: CameraInstance,
+    ctrl_id: c_int,
+) -> c_int {
+    let camera = enter!("arducam_reset_control", camera_instance);
+    let control = camera
+        .config
+        .controls
+        .iter()
+        .find(|(c, _)| c.control.id() == ctrl_id);
+    match control {
+        Some((c, _)) => {
+            lock(&camera.state).controls.insert(ctrl_id, c.default);
+            0
+        }
+        None => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_set_control(
+    camera_instance: CameraInstance,
+    ctrl_id: c_int,
+    value: c_int,
+) -> c_int {
+    let camera = enter!("arducam_set_control", camera_instance);
+    let control = camera
+        .config
+        .controls
+        .iter()
+        .find(|(c, _)| c.control.id() == ctrl_id);
+    match control {
+        Some((c, _)) if value < c.min || value > c.max => ERANGE,
+        Some(_) => {
+            lock(&camera.state).controls.insert(ctrl_id, value);
+            0
+        }
+        None => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_get_control(
+    camera_instance: CameraInstance,
+    ctrl_id: c_int,
+    value: *mut c_int,
+) -> c_int {
+    let camera = enter!("arducam_get_control", camera_instance);
+    match (lock(&camera.state).controls.get(&ctrl_id), value.is_null()) {
+        (Some(&v), false) => {
+            *value = v;
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_get_gain(
+    camera_instance: CameraInstance,
+    rgain: *mut c_int,
+    bgain: *mut c_int,
+) -> c_int {
+    let camera = enter!("arducam_get_gain", camera_instance);
+    if rgain.is_null() || bgain.is_null() {
+        return EINVAL;
+    }
+    let state = lock(&camera.state);
+    let get = |id: u32| state.controls.get(&(id as i32)).copied().unwrap_or(0);
+    *rgain = get(c::V4L2_CID_RED_BALANCE);
+    *bgain = get(c::V4L2_CID_BLUE_BALANCE);
+    0
+}
+
+pub unsafe extern "C" fn arducam_get_support_formats(
+    camera_instance: CameraInstance,
+    fmt: *mut Format,
+    index: c_int,
+) -> c_int {
+    let camera = enter!("arducam_get_support_formats", camera_instance);
+    match camera.config.modes.get(index as usize) {
+        Some((mode, desc)) if index >= 0 && !fmt.is_null() => {
+            *fmt = Format {
+                mode: index,
+                width: mode.width,
+                height: mode.height,
+                pixelformat: mode.pixel_format.fourcc(),
+                frameintervals: mode.frame_interval,
+                description: desc.as_ptr(),
+                reserved: [0; 4],
+            };
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_get_support_controls(
+    camera_instance: CameraInstance,
+    cam_ctrl: *mut CameraCtrl,
+    index: c_int,
+) -> c_int {
+    let camera = enter!("arducam_get_support_controls", camera_instance);
+    match camera.config.controls.get(index as usize) {
+        Some((control, desc)) if index >= 0 && !cam_ctrl.is_null() => {
+            *cam_ctrl = CameraCtrl {
+                id: control.control.id(),
+                desc: desc.as_ptr(),
+                max_value: control.max,
+                min_value: control.min,
+                default_value: control.default,
+            };
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+pub unsafe extern "C" fn arducam_write_sensor_reg(
+    camera_instance: CameraInstance,
+    address: u16,
+    value: u16,
+) -> c_int {
+    let camera = enter!("arducam_write_sensor_reg", camera_instance);
+    lock(&camera.state).registers.insert(address, value);
+    0
+}
+
+pub unsafe extern "C" fn arducam_read_sensor_reg(
+    camera_instance: CameraInstance,
+    address: u16,
+    value: *mut u16,
+) -> c_int {
+    let camera = enter!("arducam_read_sensor_reg", camera_instance);
+    if value.is_null() {
+        return EINVAL;
+    }
+    *value = lock(&camera.state)
+        .registers
+        .get(&address)
+        .copied()
+        .unwrap_or(0);
+    0
+}
+
+pub unsafe extern "C" fn arducam_software_auto_exposure(
+    camera_instance: CameraInstance,
+    _enable: c_int,
+) -> c_int {
+    enter!("arducam_software_auto_exposure", camera_instance);
+    0
+}
+
+pub unsafe extern "C" fn arducam_software_auto_white_balance(
+    camera_instance: CameraInstance,
+    _enable: c_int,
+) -> c_int {
+    enter!("arducam_software_auto_white_balance", camera_instance);
+    0
+}
+
+pub unsafe extern "C" fn arducam_unpack_raw10_to_raw8(
+    buff_in: *mut u8,
+    width: c_int,
+    height: c_int,
+) -> *mut Buffer {
+    if buff_in.is_null() || width <= 0 || height <= 0 {
+        return std::ptr::null_mut();
+    }
+    let (width, height) = (width as usize, height as usize);
+    let stride = raw10_stride(width);
+    let input = std::slice::from_raw_parts(buff_in, stride * height);
+    let mut output = vec![0u8; width * height];
+    match unpack_raw10_to_raw8(input, &mut output, width, height, stride) {
+        Ok(()) => new_buffer(output, 0, c::TIME_UNKNOWN),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+pub unsafe extern "C" fn arducam_unpack_raw10_to_raw16(
+    buff_in: *mut u8,
+    width: c_int,
+    height: c_int,
+) -> *mut Buffer {
+    if buff_in.is_null() || width <= 0 || height <= 0 {
+        return std::ptr::null_mut();
+    }
+    let (width, height) = (width as usize, height as usize);
+    let stride = raw10_stride(width);
+    let input = std::slice::from_raw_parts(buff_in, stride * height);
+    let mut output = vec![0u16; width * height];
+    match unpack_raw10_to_raw16(input, &mut output, width, height, stride) {
+        Ok(()) => {
+            let bytes = output.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            new_buffer(bytes, 0, c::TIME_UNKNOWN)
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+pub unsafe extern "C" fn arducam_manual_set_awb_compensation(_r_gain: u32, _b_gain: u32) {}
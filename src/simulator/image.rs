@@ -0,0 +1,333 @@
+//! Synthetic images in the formats produced by the camera.
+
+use crate::test_pattern::luma;
+use crate::{align_up, Packing, PixelFormat, TestPattern};
+
+/// The colors of one row of the image.
+fn row(pattern: TestPattern, y: usize, width: usize, height: usize) -> Vec<[u8; 3]> {
+    (0..width)
+        .map(|x| pattern.color_at(x, y, width, height).unwrap_or([0; 3]))
+        .collect()
+}
+
+fn chroma([r, g, b]: [u8; 3]) -> (u8, u8) {
+    let (r, g, b) = (i32::from(r), i32::from(g), i32::from(b));
+    let u = 128 + (-169 * r - 331 * g + 500 * b) / 1000;
+    let v = 128 + (500 * r - 419 * g - 81 * b) / 1000;
+    (u.clamp(0, 255) as u8, v.clamp(0, 255) as u8)
+}
+
+/// Raw sensor data, with rows padded to 32 bytes and the height padded to 16 rows.
+pub(super) fn raw(
+    pattern: TestPattern,
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    let bits = format.bit_depth().unwrap_or(8);
+    let packing = format.packing().unwrap_or(Packing::Unpacked);
+    let stride = format.stride(width).unwrap_or_else(|| align_up(width, 32));
+    let mut data = vec![0u8; stride * align_up(height, 16)];
+    let mut values = vec![0u16; width];
+    for y in 0..height {
+        for (x, (value, color)) in values
+            .iter_mut()
+            .zip(row(pattern, y, width, height))
+            .enumerate()
+        {
+            let v = match format.cfa_pattern() {
+                Some(cfa) => color[cfa.channel_at(x, y)],
+                None => luma(color),
+            };
+            *value = u16::from(v) << (bits - 8);
+        }
+        let out = &mut data[y * stride..][..stride];
+        match (packing, bits) {
+            (Packing::Unpacked, 8) => {
+                for (o, &v) in out.iter_mut().zip(&values) {
+                    *o = v as u8;
+                }
+            }
+            (Packing::Unpacked, _) => {
+                for (o, &v) in out.chunks_exact_mut(2).zip(&values) {
+                    o.copy_from_slice(&v.to_le_bytes());
+                }
+            }
+            (Packing::Mipi, 10) => {
+                for (o, group) in out.chunks_mut(5).zip(values.chunks(4)) {
+                    for (i, &v) in group.iter().enumerate() {
+                        o[i] = (v >> 2) as u8;
+                        o[group.len()] |= ((v & 3) as u8) << (2 * i);
+                    }
+                }
+            }
+            (Packing::Mipi, _) => {
+                for (o, group) in out.chunks_mut(3).zip(values.chunks(2)) {
+                    for (i, &v) in group.iter().enumerate() {
+                        o[i] = (v >> 4) as u8;
+                        o[group.len()] |= ((v & 0xF) as u8) << (4 * i);
+                    }
+                }
+            }
+        }
+    }
+    data
+}
+
+/// I420, with rows padded to 32 bytes and the height padded to 16 rows.
+pub(super) fn i420(pattern: TestPattern, width: usize, height: usize) -> Vec<u8> {
+    let stride = align_up(width, 32);
+    let padded_height = align_up(height, 16);
+    let y_size = stride * padded_height;
+    let uv_stride = stride / 2;
+    let uv_size = uv_stride * padded_height / 2;
+    let mut data = vec![0u8; y_size + 2 * uv_size];
+    let (y_plane, uv) = data.split_at_mut(y_size);
+    let (u_plane, v_plane) = uv.split_at_mut(uv_size);
+    for y in 0..height {
+        let colors = row(pattern, y, width, height);
+        for (o, &c) in y_plane[y * stride..].iter_mut().zip(&colors) {
+            *o = luma(c);
+        }
+        if y % 2 == 0 {
+            for (x, &c) in colors.iter().enumerate().step_by(2) {
+                let (u, v) = chroma(c);
+                u_plane[y / 2 * uv_stride + x / 2] = u;
+                v_plane[y / 2 * uv_stride + x / 2] = v;
+            }
+        }
+    }
+    data
+}
+
+/// A 24-bit BMP file.
+pub(super) fn bmp(pattern: TestPattern, width: usize, height: usize) -> Vec<u8> {
+    let stride = align_up(width * 3, 4);
+    let size = 54 + stride * height;
+    let mut data = Vec::with_capacity(size);
+    data.extend_from_slice(b"BM");
+    data.extend_from_slice(&(size as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&54u32.to_le_bytes());
+    data.extend_from_slice(&40u32.to_le_bytes());
+    data.extend_from_slice(&(width as i32).to_le_bytes());
+    data.extend_from_slice(&(height as i32).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&24u16.to_le_bytes());
+    data.extend_from_slice(&[0; 24]);
+    // Rows are stored from bottom to top, in BGR order.
+    for y in (0..height).rev() {
+        for [r, g, b] in row(pattern, y, width, height) {
+            data.extend_from_slice(&[b, g, r]);
+        }
+        data.resize(data.len() + stride - width * 3, 0);
+    }
+    data
+}
+
+/// An 8-bit RGB PNG file, without compression.
+pub(super) fn png(pattern: TestPattern, width: usize, height: usize) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for y in 0..height {
+        raw.push(0);
+        for color in row(pattern, y, width, height) {
+            raw.extend_from_slice(&color);
+        }
+    }
+    // A zlib stream of stored (uncompressed) deflate blocks.
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut data, b"IHDR", &ihdr);
+    png_chunk(&mut data, b"IDAT", &zlib);
+    png_chunk(&mut data, b"IEND", &[]);
+    data
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// A greyscale baseline JPEG file, in which every 8x8 block has a single (average) value.
+pub(super) fn jpeg(pattern: TestPattern, width: usize, height: usize, quality: i32) -> Vec<u8> {
+    let quality = quality.clamp(1, 100);
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - 2 * quality
+    };
+    let q = ((16 * scale + 50) / 100).clamp(1, 255);
+
+    let mut data = vec![0xFF, 0xD8];
+    // Quantisation table.
+    data.extend_from_slice(&[0xFF, 0xDB, 0, 67, 0]);
+    data.extend_from_slice(&[q as u8; 64]);
+    // Start of frame: 8 bits, one component.
+    data.extend_from_slice(&[0xFF, 0xC0, 0, 11, 8]);
+    data.extend_from_slice(&(height as u16).to_be_bytes());
+    data.extend_from_slice(&(width as u16).to_be_bytes());
+    data.extend_from_slice(&[1, 1, 0x11, 0]);
+    // The standard luminance DC table.
+    data.extend_from_slice(&[0xFF, 0xC4, 0, 31, 0x00]);
+    data.extend_from_slice(&DC_COUNTS);
+    data.extend_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    // An AC table with only the end-of-block code.
+    data.extend_from_slice(&[0xFF, 0xC4, 0, 20, 0x10, 1]);
+    data.extend_from_slice(&[0; 15]);
+    data.push(0x00);
+    // Start of scan.
+    data.extend_from_slice(&[0xFF, 0xDA, 0, 8, 1, 1, 0x00, 0, 63, 0]);
+
+    let dc_codes = dc_codes();
+    let lumas: Vec<Vec<u8>> = (0..height)
+        .map(|y| {
+            row(pattern, y, width, height)
+                .into_iter()
+                .map(luma)
+                .collect()
+        })
+        .collect();
+    let mut bits = BitWriter::new(&mut data);
+    let mut previous = 0;
+    for by in (0..height).step_by(8) {
+        for bx in (0..width).step_by(8) {
+            let mut sum = 0i32;
+            for y in by..by + 8 {
+                for x in bx..bx + 8 {
+                    sum += i32::from(lumas[y.min(height - 1)][x.min(width - 1)]) - 128;
+                }
+            }
+            // The DC coefficient is 8 times the mean: sum / 8.
+            let dc = (f64::from(sum) / 8.0 / f64::from(q)).round() as i32;
+            let diff = dc - previous;
+            previous = dc;
+            let size = 32 - diff.unsigned_abs().leading_zeros();
+            let (code, length) = dc_codes[size as usize];
+            bits.write(code, length);
+            let value = if diff < 0 {
+                diff + (1 << size) - 1
+            } else {
+                diff
+            };
+            bits.write(value as u32, size);
+            // End of block.
+            bits.write(0, 1);
+        }
+    }
+    bits.flush();
+    data.extend_from_slice(&[0xFF, 0xD9]);
+    data
+}
+
+/// Number of codes of each length (1 to 16) of the standard luminance DC table.
+const DC_COUNTS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+
+/// The (code, length) of each DC category.
+fn dc_codes() -> [(u32, u32); 12] {
+    let mut codes = [(0, 0); 12];
+    let mut code = 0;
+    let mut symbol = 0;
+    for (length, &count) in DC_COUNTS.iter().enumerate() {
+        for _ in 0..count {
+            codes[symbol] = (code, length as u32 + 1);
+            code += 1;
+            symbol += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+/// Writes entropy coded JPEG data, with byte stuffing.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        Self {
+            out,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, length: u32) {
+        for i in (0..length).rev() {
+            self.buffer = self.buffer << 1 | (value >> i & 1);
+            self.count += 1;
+            if self.count == 8 {
+                self.push_byte();
+            }
+        }
+    }
+
+    /// Pad the last byte with ones.
+    fn flush(&mut self) {
+        while self.count != 0 {
+            self.write(1, 1);
+        }
+    }
+
+    fn push_byte(&mut self) {
+        let byte = self.buffer as u8;
+        self.out.push(byte);
+        if byte == 0xFF {
+            self.out.push(0);
+        }
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
@@ -0,0 +1,262 @@
+//! A simulated camera, for development and testing without the hardware.
+//!
+//! When the `simulator` feature is enabled, the functions in [`c`][crate::c]
+//! are implemented in Rust instead of linking to the ArduCAM library. The
+//! simulated camera has configurable sensor modes and controls, produces
+//! synthetic frames (the [test pattern][crate::TestPattern] selected through
+//! the sensor registers, or color bars) in every [`Encoding`][crate::Encoding]
+//! and on every output, and can be told to fail or time out.
+//!
+//! The configuration and injected failures are global, and apply to all
+//! simulated cameras. Tests run in parallel by default, so tests using the
+//! simulator should hold the guard returned by [`lock`], which makes them run
+//! one at a time, each starting from the default configuration.
+//!
+//! Output callbacks are called from a separate thread for every camera, at
+//! the frame rate of the current mode. Changing the outputs from within a
+//! callback deadlocks.
+
+use crate::sensor::Sensor;
+use crate::{Control, Fract, PixelFormat};
+use std::ffi::CStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub(crate) mod ffi;
+mod image;
+#[cfg(test)]
+mod tests;
+
+/// Configuration of the simulated cameras, see [`configure`].
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    /// The sensor to report through the chip id registers, if any.
+    pub sensor: Option<Sensor>,
+    /// The sensor modes. The first one is the default.
+    pub modes: Vec<SimulatedMode>,
+    /// The supported controls.
+    pub controls: Vec<SimulatedControl>,
+}
+
+/// A sensor mode of the simulated camera.
+#[derive(Debug, Clone)]
+pub struct SimulatedMode {
+    /// Width in pixels.
+    pub width: i32,
+    /// Height in pixels.
+    pub height: i32,
+    /// The pixel format of the raw sensor data.
+    pub pixel_format: PixelFormat,
+    /// The time between frames, as a fraction of seconds.
+    pub frame_interval: Fract,
+    /// Human readable description of the mode.
+    pub description: String,
+}
+
+/// A control of the simulated camera.
+#[derive(Debug, Clone)]
+pub struct SimulatedControl {
+    /// The control.
+    pub control: Control,
+    /// Human readable name of the control.
+    pub name: String,
+    /// The minimum value.
+    pub min: i32,
+    /// The maximum value.
+    pub max: i32,
+    /// The default value.
+    pub default: i32,
+}
+
+impl SimulatedMode {
+    /// A mode with a description generated from the resolution and frame rate.
+    pub fn new(width: i32, height: i32, pixel_format: PixelFormat, frame_rate: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixel_format,
+            frame_interval: Fract {
+                numerator: 1,
+                denominator: frame_rate,
+            },
+            description: format!("width: {}, height: {}, {} fps", width, height, frame_rate),
+        }
+    }
+}
+
+impl SimulatedControl {
+    /// A control with the given range and default value.
+    pub fn new(control: Control, name: &str, min: i32, max: i32, default: i32) -> Self {
+        Self {
+            control,
+            name: name.to_string(),
+            min,
+            max,
+            default,
+        }
+    }
+}
+
+impl Default for SimulatorConfig {
+    /// An IMX219 with a few common modes and controls.
+    fn default() -> Self {
+        Self {
+            sensor: Some(Sensor::Imx219),
+            modes: vec![
+                SimulatedMode::new(1920, 1080, PixelFormat::Bggr10Packed, 30),
+                SimulatedMode::new(3280, 2464, PixelFormat::Bggr10Packed, 15),
+                SimulatedMode::new(1640, 1232, PixelFormat::Bggr10Packed, 30),
+                SimulatedMode::new(640, 480, PixelFormat::Bggr10Packed, 60),
+            ],
+            controls: vec![
+                SimulatedControl::new(Control::Exposure, "Exposure", 4, 3522, 1600),
+                SimulatedControl::new(Control::Gain, "Analogue Gain", 0, 232, 0),
+                SimulatedControl::new(Control::HFlip, "Horizontal Flip", 0, 1, 0),
+                SimulatedControl::new(Control::VFlip, "Vertical Flip", 0, 1, 0),
+            ],
+        }
+    }
+}
+
+/// Set the configuration of simulated cameras initialized after this call.
+pub fn configure(config: SimulatorConfig) {
+    global().config = Some(Arc::new(Compiled::new(config)));
+}
+
+/// Make the next call to the given C function (such as `"arducam_set_control"`)
+/// fail with the given error code.
+///
+/// For `arducam_capture`, the code is ignored and the capture times out.
+pub fn fail_next(function: &'static str, code: i32) {
+    global().failures.push(Failure {
+        function,
+        code,
+        once: true,
+    });
+}
+
+/// Make all calls to the given C function fail with the given error code,
+/// until [`reset`] is called.
+pub fn fail_always(function: &'static str, code: i32) {
+    global().failures.push(Failure {
+        function,
+        code,
+        once: false,
+    });
+}
+
+/// Make the next capture time out, which makes it return no buffer.
+pub fn timeout_next_capture() {
+    fail_next("arducam_capture", 0);
+}
+
+/// Restore the default configuration, and remove all injected failures.
+pub fn reset() {
+    let mut global = global();
+    global.config = None;
+    global.failures.clear();
+}
+
+/// Guard returned by [`lock`].
+///
+/// Calls [`reset`] when dropped, before the next thread gets the lock.
+#[must_use]
+pub struct SimulatorLock {
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// Get exclusive use of the simulator, blocking while another thread holds it,
+/// and [`reset`] it.
+///
+/// This does not prevent other threads from calling the functions of this module,
+/// or from using simulated cameras; it only works if every user takes the lock.
+pub fn lock() -> SimulatorLock {
+    static EXCLUSIVE: Mutex<()> = Mutex::new(());
+    // A test that panicked while holding the lock poisons it, which is fine here.
+    let guard = EXCLUSIVE.lock().unwrap_or_else(|e| e.into_inner());
+    reset();
+    SimulatorLock { _guard: guard }
+}
+
+impl Drop for SimulatorLock {
+    fn drop(&mut self) {
+        reset();
+    }
+}
+
+/// The configuration, with the strings converted for the C interface.
+pub(crate) struct Compiled {
+    sensor: Option<Sensor>,
+    modes: Vec<(SimulatedMode, &'static CStr)>,
+    controls: Vec<(SimulatedControl, &'static CStr)>,
+}
+
+impl Compiled {
+    fn new(config: SimulatorConfig) -> Self {
+        // The library returns pointers to static strings, so these are leaked.
+        let leak = |s: &str| -> &'static CStr {
+            let s = std::ffi::CString::new(s.replace('\0', "")).unwrap_or_default();
+            Box::leak(s.into_boxed_c_str())
+        };
+        Self {
+            sensor: config.sensor,
+            modes: config
+                .modes
+                .into_iter()
+                .map(|m| {
+                    let desc = leak(&m.description);
+                    (m, desc)
+                })
+                .collect(),
+            controls: config
+                .controls
+                .into_iter()
+                .map(|c| {
+                    let desc = leak(&c.name);
+                    (c, desc)
+                })
+                .collect(),
+        }
+    }
+}
+
+struct Failure {
+    function: &'static str,
+    code: i32,
+    once: bool,
+}
+
+struct Global {
+    config: Option<Arc<Compiled>>,
+    failures: Vec<Failure>,
+}
+
+static GLOBAL: Mutex<Global> = Mutex::new(Global {
+    config: None,
+    failures: Vec::new(),
+});
+
+fn global() -> MutexGuard<'static, Global> {
+    GLOBAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The current configuration.
+fn config() -> Arc<Compiled> {
+    global()
+        .config
+        .get_or_insert_with(|| Arc::new(Compiled::new(SimulatorConfig::default())))
+        .clone()
+}
+
+/// Take the injected failure for the given function, if any.
+fn failure(function: &str) -> Option<i32> {
+    let mut global = global();
+    let index = global
+        .failures
+        .iter()
+        .position(|f| f.function == function)?;
+    let code = global.failures[index].code;
+    if global.failures[index].once {
+        global.failures.remove(index);
+    }
+    Some(code)
+}
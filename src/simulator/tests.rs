@@ -0,0 +1,392 @@
+use super::*;
+use crate::{
+    Camera, Encoding, ErrorKind, OutputKind, PixelLayout, SharedCamera, TestPattern,
+    VideoEncoderConfig,
+};
+use std::sync::mpsc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A camera with small modes, so that frames are cheap to produce.
+fn small_camera() -> Camera {
+    configure(SimulatorConfig {
+        modes: vec![
+            SimulatedMode::new(64, 48, PixelFormat::Bggr10Packed, 100),
+            SimulatedMode::new(32, 24, PixelFormat::Rggb12Packed, 100),
+        ],
+        ..SimulatorConfig::default()
+    });
+    Camera::init(None).unwrap()
+}
+
+#[test]
+fn default_config() {
+    let _lock = lock();
+    let mut camera = Camera::init(None).unwrap();
+    assert_eq!(Sensor::detect(&mut camera), Some(Sensor::Imx219));
+    let format = camera.get_format().unwrap();
+    assert_eq!((format.width, format.height), (1920, 1080));
+    assert_eq!(format.pixel_format(), PixelFormat::Bggr10Packed);
+    let modes = camera.supported_formats().unwrap();
+    assert_eq!(modes.len(), 4);
+    assert_eq!(modes[3].width, 640);
+    let controls = camera.supported_controls().unwrap();
+    assert_eq!(controls.len(), 4);
+    assert_eq!(camera.get_control(Control::Exposure).unwrap(), 1600);
+    camera.close().unwrap();
+}
+
+#[test]
+fn controls() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    camera.set_control(Control::Exposure, 100).unwrap();
+    assert_eq!(camera.get_control(Control::Exposure).unwrap(), 100);
+    camera.reset_control(Control::Exposure).unwrap();
+    assert_eq!(camera.get_control(Control::Exposure).unwrap(), 1600);
+    let e = camera.set_control(Control::Exposure, 5000).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+    let e = camera
+        .set_control_checked(Control::Exposure, 5000)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+    // The simulated IMX219 only has `Control::Exposure`, like the real one.
+    let e = camera
+        .set_exposure_time(Duration::from_millis(10))
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Unsupported);
+    assert_eq!(
+        camera.exposure_time().unwrap_err().kind(),
+        ErrorKind::Unsupported
+    );
+}
+
+#[test]
+fn exposure_time() {
+    let _lock = lock();
+    let mut config = SimulatorConfig::default();
+    config.controls.push(SimulatedControl::new(
+        Control::ExposureAbsolute,
+        "Exposure Time, Absolute",
+        1,
+        1000,
+        100,
+    ));
+    configure(config);
+    let mut camera = Camera::init(None).unwrap();
+    camera
+        .set_exposure_time(Duration::from_micros(12_345))
+        .unwrap();
+    assert_eq!(camera.get_control(Control::ExposureAbsolute).unwrap(), 123);
+    assert_eq!(
+        camera.exposure_time().unwrap(),
+        Duration::from_millis(12) + Duration::from_micros(300)
+    );
+    let e = camera
+        .set_exposure_time(Duration::from_secs(1))
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+    assert_eq!(e.operation(), "Camera::set_exposure_time");
+    let e = camera
+        .set_control_checked(Control::ExposureAbsolute, 0)
+        .unwrap_err();
+    assert_eq!(e.operation(), "Camera::set_control_checked");
+}
+
+#[test]
+fn modes() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    camera.set_mode(1).unwrap();
+    let format = camera.get_format().unwrap();
+    assert_eq!((format.width, format.height), (32, 24));
+    assert_eq!(format.pixel_format(), PixelFormat::Rggb12Packed);
+    // The smallest mode that covers the resolution is used, and scaled.
+    assert_eq!(camera.set_resolution(60, 40).unwrap(), (60, 40));
+    let format = camera.get_format().unwrap();
+    assert_eq!((format.mode, format.width, format.height), (0, 60, 40));
+    let e = camera.set_mode(2).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+}
+
+#[test]
+fn capture() {
+    let _lock = lock();
+    let camera = small_camera();
+    let frame = camera.capture(1000, Encoding::I420, 90).unwrap();
+    let layout = *frame.layout();
+    assert_eq!(layout.pixels, PixelLayout::I420);
+    assert_eq!((layout.width, layout.height), (64, 48));
+    assert_eq!(frame.data().len(), 64 * 48 * 3 / 2);
+    assert!(TestPattern::ColorBars.luma_error(&frame).unwrap() < 1.0);
+
+    let frame = camera.capture(1000, Encoding::RawBayer, 90).unwrap();
+    let layout = *frame.layout();
+    assert_eq!(layout.pixels, PixelLayout::Raw(PixelFormat::Bggr10Packed));
+    assert_eq!(layout.stride, 96);
+    assert!(frame.data().len() >= layout.stride * layout.height);
+
+    let jpeg = camera.capture(1000, Encoding::Jpeg, 90).unwrap();
+    assert_eq!(jpeg.layout().pixels, PixelLayout::Encoded);
+    assert!(jpeg.data().starts_with(&[0xff, 0xd8]));
+    assert!(jpeg.data().ends_with(&[0xff, 0xd9]));
+    let png = camera.capture(1000, Encoding::Png, 90).unwrap();
+    assert!(png.data().starts_with(b"\x89PNG\r\n\x1a\n"));
+    let bmp = camera.capture(1000, Encoding::Bmp, 90).unwrap();
+    assert!(bmp.data().starts_with(b"BM"));
+}
+
+#[test]
+fn test_pattern() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    let pattern = TestPattern::SolidColor {
+        red: 200,
+        green: 100,
+        blue: 50,
+    };
+    camera.set_test_pattern(pattern).unwrap();
+    let frame = camera.capture(1000, Encoding::I420, 90).unwrap();
+    assert!(pattern.luma_error(&frame).unwrap() < 2.0);
+    assert!(TestPattern::ColorBars.luma_error(&frame).unwrap() > 10.0);
+}
+
+#[test]
+fn callbacks() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    let (sender, receiver) = mpsc::channel();
+    let stream = camera
+        .start_yuv_stream(move |frame| {
+            assert!(frame.is_frame_end() && !frame.is_corrupted());
+            let _ = sender.send((frame.width(), frame.height(), frame.y().len()));
+        })
+        .unwrap();
+    assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap(), (64, 48, 64 * 48));
+    assert_eq!(stream.kind(), OutputKind::Yuv);
+    stream.stop().unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let stream = camera
+        .start_raw_stream(move |frame| {
+            assert!(frame.is_frame_end() && !frame.is_corrupted());
+            assert_eq!(frame.pixel_format(), PixelFormat::Bggr10Packed);
+            assert_eq!(frame.stride(), Some(96));
+            let _ = sender.send((frame.width(), frame.data().len()));
+        })
+        .unwrap();
+    let (width, length) = receiver.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(width, 64);
+    assert!(length >= 96 * 48);
+    drop(stream);
+
+    let (sender, receiver) = mpsc::channel();
+    let stream = camera
+        .start_video(VideoEncoderConfig::default(), move |chunk| {
+            let _ = sender.send((chunk.is_config(), chunk.is_keyframe()));
+        })
+        .unwrap();
+    assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap(), (true, false));
+    assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap(), (false, true));
+    drop(stream);
+    camera.close().unwrap();
+}
+
+#[test]
+fn output_busy() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    let mut stream = camera.start_yuv_stream(|_| {}).unwrap();
+    let e = stream.start_yuv_stream(|_| {}).err().unwrap();
+    assert_eq!(e.kind(), ErrorKind::Busy);
+    // A different output can run at the same time.
+    stream.start_raw_stream(|_| {}).unwrap();
+}
+
+#[test]
+fn format_locked_while_streaming() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    let mut stream = camera.start_raw_stream(|_| {}).unwrap();
+    assert_eq!(stream.set_mode(1).unwrap_err().kind(), ErrorKind::Busy);
+    let e = stream.set_resolution(32, 24).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Busy);
+    stream.set_control(Control::Gain, 10).unwrap();
+    drop(stream);
+    camera.set_mode(1).unwrap();
+}
+
+#[test]
+fn frames() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    let mut frames = camera.frames(OutputKind::Raw, 4).unwrap();
+    frames.set_timeout(Some(TIMEOUT));
+    let first = frames.next().unwrap().unwrap();
+    let second = frames.next().unwrap().unwrap();
+    assert_eq!(first.kind(), Some(OutputKind::Raw));
+    assert_eq!(first.width(), 64);
+    assert!(second.timestamp().unwrap() > first.timestamp().unwrap());
+    frames.stop().unwrap();
+
+    let mut frames = camera.frames(OutputKind::Video, 4).unwrap();
+    frames.set_timeout(Some(TIMEOUT));
+    assert!(frames.next().unwrap().unwrap().flags().is_config());
+    drop(frames);
+    // The output was stopped, so it can be started again.
+    camera.frames(OutputKind::Video, 4).unwrap();
+}
+
+#[test]
+fn shared() {
+    let _lock = lock();
+    let camera = SharedCamera::new(small_camera());
+    let mut frames = camera.frames(OutputKind::Yuv, 4).unwrap();
+    frames.set_timeout(Some(TIMEOUT));
+    let controls = camera.clone();
+    let thread = std::thread::spawn(move || controls.set_control(Control::Exposure, 100));
+    assert_eq!(frames.next().unwrap().unwrap().height(), 48);
+    thread.join().unwrap().unwrap();
+    assert_eq!(camera.get_control(Control::Exposure).unwrap(), 100);
+    frames.stop().unwrap();
+}
+
+#[test]
+fn shared_frames_dropped_while_locked() {
+    let _lock = lock();
+    let camera = SharedCamera::new(small_camera());
+    let frames = camera.frames(OutputKind::Yuv, 4).unwrap();
+    {
+        let mut guard = camera.lock();
+        drop(frames);
+        // Still running, since this thread has the camera locked.
+        let e = guard.start_yuv_stream(|_| {}).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::Busy);
+    }
+    // Stopped when the guard was dropped.
+    drop(camera.lock().start_yuv_stream(|_| {}).unwrap());
+
+    // Another thread holding the camera only delays the stop.
+    let frames = camera.frames(OutputKind::Yuv, 4).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let other = camera.clone();
+    let thread = std::thread::spawn(move || {
+        let _guard = other.lock();
+        sender.send(()).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+    });
+    receiver.recv().unwrap();
+    drop(frames);
+    thread.join().unwrap();
+    drop(camera.lock().start_yuv_stream(|_| {}).unwrap());
+}
+
+#[test]
+fn injected_failures() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    fail_next("arducam_set_mode", -16);
+    let e = camera.set_mode(1).unwrap_err();
+    assert_eq!((e.kind(), e.code()), (ErrorKind::Busy, Some(-16)));
+    camera.set_mode(1).unwrap();
+
+    fail_always("arducam_set_control", -5);
+    for _ in 0..2 {
+        let e = camera.set_control(Control::Gain, 1).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Io);
+    }
+    reset();
+    camera.set_control(Control::Gain, 1).unwrap();
+
+    timeout_next_capture();
+    let e = camera.capture(1000, Encoding::I420, 90).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Other);
+    camera.capture(1000, Encoding::I420, 90).unwrap();
+
+    fail_next("arducam_set_yuv_callback", -22);
+    let e = camera.start_yuv_stream(|_| {}).err().unwrap();
+    assert_eq!(e.kind(), ErrorKind::InvalidArgument);
+    drop(camera.start_yuv_stream(|_| {}).unwrap());
+
+    fail_next("arducam_init_camera", -19);
+    assert!(Camera::init(None).is_err());
+}
+
+#[test]
+fn close() {
+    let _lock = lock();
+    let mut camera = small_camera();
+    let _ = camera.frames(OutputKind::Yuv, 4).unwrap();
+    camera.close().unwrap();
+
+    let camera = small_camera();
+    fail_next("arducam_close_camera", -5);
+    let e = camera.close().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Io);
+
+    // Dropping closes the camera too, and only logs errors.
+    let camera = small_camera();
+    fail_next("arducam_close_camera", -5);
+    drop(camera);
+}
+
+#[test]
+fn no_modes() {
+    let _lock = lock();
+    configure(SimulatorConfig {
+        modes: Vec::new(),
+        ..SimulatorConfig::default()
+    });
+    let mut camera = Camera::init(None).unwrap();
+    assert_eq!(
+        camera.get_format().unwrap_err().kind(),
+        ErrorKind::InvalidArgument
+    );
+    assert!(camera.capture_buffer(1000, Encoding::I420, 90).is_err());
+    assert!(camera.supported_formats().is_err());
+    // The camera thread keeps running without producing frames.
+    std::thread::sleep(Duration::from_millis(50));
+    camera.close().unwrap();
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn shared_frame_stream() {
+    use futures_core::Stream;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+
+    /// Wakes a thread blocked in `next`.
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::new(&mut *stream).poll_next(&mut cx) {
+                Poll::Ready(item) => return item,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    let _lock = lock();
+    let camera = SharedCamera::new(small_camera());
+    let mut stream = camera.frames(OutputKind::Yuv, 4).unwrap().into_stream();
+    // The stream is owned, so it can be moved to another thread or task.
+    let thread = std::thread::spawn(move || {
+        let frame = next(&mut stream).unwrap().unwrap();
+        stream.stop().unwrap();
+        frame
+    });
+    assert_eq!(thread.join().unwrap().width(), 64);
+    drop(camera.lock().start_yuv_stream(|_| {}).unwrap());
+}
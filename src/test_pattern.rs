@@ -0,0 +1,240 @@
+//! Sensor test patterns.
+
+use crate::sensor::{imx219, imx477, ov5647, ov9281, Register, Sensor};
+use crate::{Camera, Error, ErrorKind, Frame, PixelLayout, Result};
+
+/// A test pattern generated by the sensor instead of the image, used by [`Camera::set_test_pattern`].
+///
+/// The expected image can be generated with [`TestPattern::expected_rgb`] or
+/// [`TestPattern::expected_luma`], to validate the processing pipeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestPattern {
+    /// No test pattern, the normal image.
+    Disabled,
+    /// Eight vertical color bars: white, yellow, cyan, green, magenta, red, blue and black.
+    ColorBars,
+    /// The color bars, fading to grey from top to bottom.
+    Gradient,
+    /// A single color, with 8-bit components.
+    SolidColor {
+        /// Red component.
+        red: u8,
+        /// Green component.
+        green: u8,
+        /// Blue component.
+        blue: u8,
+    },
+}
+
+/// The colors of the color bars, from left to right.
+const COLOR_BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+
+impl TestPattern {
+    /// The expected color of the pixel at (`x`, `y`) in a frame of `width` by `height` pixels.
+    ///
+    /// Returns `None` for [`TestPattern::Disabled`].
+    pub fn color_at(&self, x: usize, y: usize, width: usize, height: usize) -> Option<[u8; 3]> {
+        let bar = |x: usize| COLOR_BARS[(x * COLOR_BARS.len() / width.max(1)).min(7)];
+        match *self {
+            TestPattern::Disabled => None,
+            TestPattern::ColorBars => Some(bar(x)),
+            TestPattern::Gradient => {
+                let bottom = height.saturating_sub(1).max(1);
+                let y = y.min(bottom);
+                let fade = |v: u8| ((usize::from(v) * (bottom - y) + 128 * y) / bottom) as u8;
+                let [r, g, b] = bar(x);
+                Some([fade(r), fade(g), fade(b)])
+            }
+            TestPattern::SolidColor { red, green, blue } => Some([red, green, blue]),
+        }
+    }
+
+    /// Generate the expected image, as 8-bit RGB in row-major order without padding.
+    ///
+    /// Returns `None` for [`TestPattern::Disabled`].
+    pub fn expected_rgb(&self, width: usize, height: usize) -> Option<Vec<u8>> {
+        if *self == TestPattern::Disabled {
+            return None;
+        }
+        let mut image = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                image.extend_from_slice(&self.color_at(x, y, width, height)?);
+            }
+        }
+        Some(image)
+    }
+
+    /// Generate the expected luma (BT.601), in row-major order without padding.
+    ///
+    /// Returns `None` for [`TestPattern::Disabled`].
+    pub fn expected_luma(&self, width: usize, height: usize) -> Option<Vec<u8>> {
+        if *self == TestPattern::Disabled {
+            return None;
+        }
+        let mut image = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                image.push(luma(self.color_at(x, y, width, height)?));
+            }
+        }
+        Some(image)
+    }
+
+    /// Compare an I420 frame, such as a capture with [`Encoding::I420`][crate::Encoding::I420], with the expected image.
+    ///
+    /// Returns the mean absolute difference of the luma, from 0 (identical)
+    /// to 255. The image processing of the camera (white balance, gamma, etc.)
+    /// changes the colors somewhat, so a small difference is to be expected.
+    ///
+    /// Returns an error of kind [`InvalidArgument`][ErrorKind::InvalidArgument]
+    /// for [`TestPattern::Disabled`], if the frame is not I420, or if the buffer
+    /// is too small for the resolution.
+    pub fn luma_error<D: AsRef<[u8]>>(&self, frame: &Frame<D>) -> Result<f64> {
+        let invalid = |message: String| {
+            Error::with_message(
+                ErrorKind::InvalidArgument,
+                "TestPattern::luma_error",
+                message,
+            )
+        };
+        if frame.layout().pixels != PixelLayout::I420 {
+            return Err(invalid(format!(
+                "expected an I420 frame, got {:?}",
+                frame.layout().pixels
+            )));
+        }
+        let (width, height) = (frame.width(), frame.height());
+        let expected = self
+            .expected_luma(width, height)
+            .ok_or_else(|| invalid("no test pattern to compare with".into()))?;
+        if width == 0 || height == 0 || frame.rows().count() < height {
+            return Err(invalid(format!(
+                "buffer of {} bytes is too small for a {}x{} I420 image",
+                frame.data().len(),
+                width,
+                height
+            )));
+        }
+        let mut total = 0u64;
+        for (actual, expected) in frame.rows().zip(expected.chunks(width)) {
+            for (&a, &e) in actual.iter().zip(expected) {
+                total += u64::from(a.abs_diff(e));
+            }
+        }
+        Ok(total as f64 / (width * height) as f64)
+    }
+}
+
+impl Camera {
+    /// Replace the image by a test pattern generated by the sensor.
+    ///
+    /// This writes the sensor registers directly, see [`sensor`][crate::sensor].
+    /// Only the sensors in [`Sensor`] are supported, and not every sensor supports every pattern:
+    /// the OmniVision sensors only support [`TestPattern::ColorBars`], and a black
+    /// [`TestPattern::SolidColor`] on the OV5647.
+    ///
+    /// Returns an error of kind [`Unsupported`][ErrorKind::Unsupported] if the
+    /// sensor or pattern is not supported.
+    pub fn set_test_pattern(&mut self, pattern: TestPattern) -> Result<()> {
+        let sensor = Sensor::detect(self).ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::Unsupported,
+                "Camera::set_test_pattern",
+                "unknown sensor",
+            )
+        })?;
+        let unsupported = || {
+            Err(Error::with_message(
+                ErrorKind::Unsupported,
+                "Camera::set_test_pattern",
+                format!("{:?} is not supported by the {:?}", pattern, sensor),
+            ))
+        };
+        match (sensor, pattern) {
+            (Sensor::Imx219, _) => set_smia_test_pattern(self, pattern, Smia::IMX219),
+            (Sensor::Imx477, _) => set_smia_test_pattern(self, pattern, Smia::IMX477),
+            (Sensor::Ov5647, TestPattern::Disabled) => ov5647::TEST_PATTERN.write(self, 0),
+            (Sensor::Ov5647, TestPattern::ColorBars) => ov5647::TEST_PATTERN.write(
+                self,
+                1 << ov5647::TEST_PATTERN_ENABLE.shift | ov5647::TEST_PATTERN_COLOR_BAR,
+            ),
+            (
+                Sensor::Ov5647,
+                TestPattern::SolidColor {
+                    red: 0,
+                    green: 0,
+                    blue: 0,
+                },
+            ) => ov5647::TEST_PATTERN.write(
+                self,
+                1 << ov5647::TEST_PATTERN_ENABLE.shift | ov5647::TEST_PATTERN_BLACK,
+            ),
+            (Sensor::Ov9281, TestPattern::Disabled) => ov9281::TEST_PATTERN.write(self, 0),
+            (Sensor::Ov9281, TestPattern::ColorBars) => {
+                ov9281::TEST_PATTERN.write(self, 1 << ov9281::TEST_PATTERN_ENABLE.shift)
+            }
+            _ => unsupported(),
+        }
+    }
+}
+
+/// The test pattern registers of the Sony sensors, which follow the SMIA layout.
+struct Smia {
+    mode: Register,
+    red: Register,
+    green_r: Register,
+    blue: Register,
+    green_b: Register,
+}
+
+impl Smia {
+    const IMX219: Smia = Smia {
+        mode: imx219::TEST_PATTERN,
+        red: imx219::TEST_DATA_RED,
+        green_r: imx219::TEST_DATA_GREEN_R,
+        blue: imx219::TEST_DATA_BLUE,
+        green_b: imx219::TEST_DATA_GREEN_B,
+    };
+
+    const IMX477: Smia = Smia {
+        mode: imx477::TEST_PATTERN,
+        red: imx477::TEST_DATA_RED,
+        green_r: imx477::TEST_DATA_GREEN_R,
+        blue: imx477::TEST_DATA_BLUE,
+        green_b: imx477::TEST_DATA_GREEN_B,
+    };
+}
+
+fn set_smia_test_pattern(camera: &mut Camera, pattern: TestPattern, regs: Smia) -> Result<()> {
+    // The mode values are the same for both sensors.
+    let mode = match pattern {
+        TestPattern::Disabled => imx219::TEST_PATTERN_DISABLED,
+        TestPattern::ColorBars => imx219::TEST_PATTERN_COLOR_BARS,
+        TestPattern::Gradient => imx219::TEST_PATTERN_GREY_COLOR_BARS,
+        TestPattern::SolidColor { red, green, blue } => {
+            // Scale the 8-bit components to the bit depth of the sensor.
+            let scale = |v: u8| u32::from(v) << (regs.red.bits - 8);
+            regs.red.write(camera, scale(red))?;
+            regs.green_r.write(camera, scale(green))?;
+            regs.blue.write(camera, scale(blue))?;
+            regs.green_b.write(camera, scale(green))?;
+            imx219::TEST_PATTERN_SOLID_COLOR
+        }
+    };
+    regs.mode.write(camera, mode)
+}
+
+/// BT.601 luma of an RGB color, with full range.
+pub(crate) fn luma([r, g, b]: [u8; 3]) -> u8 {
+    ((299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b) + 500) / 1000) as u8
+}
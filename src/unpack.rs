@@ -0,0 +1,363 @@
+//! Unpacking of MIPI packed raw data.
+//!
+//! MIPI RAW10 stores four pixels in five bytes: first the 8 most significant
+//! bits of each pixel, then one byte with the 2 least significant bits of the
+//! four pixels. MIPI RAW12 stores two pixels in three bytes: the 8 most
+//! significant bits of each pixel, then one byte with the 4 least significant
+//! bits of both.
+//!
+//! Rows in the buffers of the camera are padded to a multiple of 32 bytes, and
+//! the height is padded to a multiple of 16 rows (so 1080 becomes 1088). The
+//! functions in this module take the stride (the number of bytes between the
+//! start of two rows) of the input, and only write the unpadded image: `width`
+//! times `height` pixels, without any padding between the rows.
+//!
+//! These are a faster alternative to
+//! [`arducam_unpack_raw10_to_raw8`][crate::c::arducam_unpack_raw10_to_raw8] and
+//! [`arducam_unpack_raw10_to_raw16`][crate::c::arducam_unpack_raw10_to_raw16],
+//! and write into a caller-provided buffer instead of allocating.
+
+use crate::{align_up, Error, ErrorKind, Packing, PixelFormat, Result};
+
+/// The stride of MIPI RAW10 rows of `width` pixels in the buffers of the camera.
+pub fn raw10_stride(width: usize) -> usize {
+    align_up((width * 5).div_ceil(4), 32)
+}
+
+/// The stride of MIPI RAW12 rows of `width` pixels in the buffers of the camera.
+pub fn raw12_stride(width: usize) -> usize {
+    align_up((width * 3).div_ceil(2), 32)
+}
+
+/// Unpack MIPI RAW10 to 8 bits per pixel, dropping the 2 least significant bits.
+pub fn unpack_raw10_to_raw8(
+    input: &[u8],
+    output: &mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Result<()> {
+    let op = "unpack_raw10_to_raw8";
+    check(op, input.len(), output.len(), width, height, stride, 5, 4)?;
+    for (row, out) in rows(input, output, width, height, stride) {
+        raw10_row_to_raw8(row, out);
+    }
+    Ok(())
+}
+
+/// Unpack MIPI RAW10 to 16 bits per pixel. The values are in the range 0 to 1023.
+pub fn unpack_raw10_to_raw16(
+    input: &[u8],
+    output: &mut [u16],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Result<()> {
+    let op = "unpack_raw10_to_raw16";
+    check(op, input.len(), output.len(), width, height, stride, 5, 4)?;
+    for (row, out) in rows(input, output, width, height, stride) {
+        raw10_row_to_raw16(row, out);
+    }
+    Ok(())
+}
+
+/// Unpack MIPI RAW12 to 8 bits per pixel, dropping the 4 least significant bits.
+pub fn unpack_raw12_to_raw8(
+    input: &[u8],
+    output: &mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Result<()> {
+    let op = "unpack_raw12_to_raw8";
+    check(op, input.len(), output.len(), width, height, stride, 3, 2)?;
+    for (row, out) in rows(input, output, width, height, stride) {
+        raw12_row_to_raw8(row, out);
+    }
+    Ok(())
+}
+
+/// Unpack MIPI RAW12 to 16 bits per pixel. The values are in the range 0 to 4095.
+pub fn unpack_raw12_to_raw16(
+    input: &[u8],
+    output: &mut [u16],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Result<()> {
+    let op = "unpack_raw12_to_raw16";
+    check(op, input.len(), output.len(), width, height, stride, 3, 2)?;
+    for (row, out) in rows(input, output, width, height, stride) {
+        raw12_row_to_raw16(row, out);
+    }
+    Ok(())
+}
+
+/// Unpack raw data in any known [`PixelFormat`] to 16 bits per pixel, with
+/// rows padded to [`PixelFormat::stride`]. The values keep the bit depth of the format.
+///
+/// Returns an error of kind [`Unsupported`][ErrorKind::Unsupported] for unknown formats.
+pub fn unpack_to_raw16(
+    input: &[u8],
+    output: &mut [u16],
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+) -> Result<()> {
+    let op = "unpack_to_raw16";
+    let (bits, packing, stride) = match (format.bit_depth(), format.packing(), format.stride(width))
+    {
+        (Some(bits), Some(packing), Some(stride)) => (bits, packing, stride),
+        _ => {
+            return Err(Error::with_message(
+                ErrorKind::Unsupported,
+                op,
+                format!("unknown pixel format {:?}", format),
+            ))
+        }
+    };
+    match (packing, bits) {
+        (Packing::Mipi, 10) => unpack_raw10_to_raw16(input, output, width, height, stride),
+        (Packing::Mipi, _) => unpack_raw12_to_raw16(input, output, width, height, stride),
+        (Packing::Unpacked, 8) => {
+            check(op, input.len(), output.len(), width, height, stride, 1, 1)?;
+            for (row, out) in rows(input, output, width, height, stride) {
+                for (o, &i) in out.iter_mut().zip(row) {
+                    *o = u16::from(i);
+                }
+            }
+            Ok(())
+        }
+        (Packing::Unpacked, _) => {
+            check(op, input.len(), output.len(), width, height, stride, 2, 1)?;
+            for (row, out) in rows(input, output, width, height, stride) {
+                for (o, i) in out.iter_mut().zip(row.chunks_exact(2)) {
+                    *o = u16::from_le_bytes([i[0], i[1]]);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Check the sizes of the buffers, for a packing of `pixels` pixels in `bytes` bytes.
+#[allow(clippy::too_many_arguments)]
+fn check(
+    op: &'static str,
+    input: usize,
+    output: usize,
+    width: usize,
+    height: usize,
+    stride: usize,
+    bytes: usize,
+    pixels: usize,
+) -> Result<()> {
+    let invalid = |message: String| Error::with_message(ErrorKind::InvalidArgument, op, message);
+    let row_bytes = (width * bytes).div_ceil(pixels);
+    if stride < row_bytes {
+        return Err(invalid(format!(
+            "stride of {} bytes is too small for rows of {} pixels",
+            stride, width
+        )));
+    }
+    if height > 0 && input < stride * (height - 1) + row_bytes {
+        return Err(invalid(format!(
+            "input of {} bytes is too small for {}x{} pixels with a stride of {} bytes",
+            input, width, height, stride
+        )));
+    }
+    if output < width * height {
+        return Err(invalid(format!(
+            "output of {} pixels is too small for {}x{} pixels",
+            output, width, height
+        )));
+    }
+    Ok(())
+}
+
+/// Iterate over the input and output rows.
+fn rows<'a, T>(
+    input: &'a [u8],
+    output: &'a mut [T],
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> impl Iterator<Item = (&'a [u8], &'a mut [T])> {
+    input
+        .chunks(stride.max(1))
+        .zip(output.chunks_exact_mut(width.max(1)))
+        .take(if width == 0 { 0 } else { height })
+}
+
+fn raw10_row_to_raw8(row: &[u8], out: &mut [u8]) {
+    let full = out.len() / 4;
+    for (i, o) in row.chunks_exact(5).zip(out.chunks_exact_mut(4)) {
+        o.copy_from_slice(&i[..4]);
+    }
+    let rest = &mut out[full * 4..];
+    rest.copy_from_slice(&row[full * 5..][..rest.len()]);
+}
+
+fn raw10_row_to_raw16(row: &[u8], out: &mut [u16]) {
+    let full = out.len() / 4;
+    for (i, o) in row.chunks_exact(5).zip(out.chunks_exact_mut(4)) {
+        let low = i[4];
+        o[0] = u16::from(i[0]) << 2 | u16::from(low & 3);
+        o[1] = u16::from(i[1]) << 2 | u16::from(low >> 2 & 3);
+        o[2] = u16::from(i[2]) << 2 | u16::from(low >> 4 & 3);
+        o[3] = u16::from(i[3]) << 2 | u16::from(low >> 6);
+    }
+    let rest = &mut out[full * 4..];
+    if !rest.is_empty() {
+        let i = &row[full * 5..];
+        let low = i[rest.len()];
+        for (n, o) in rest.iter_mut().enumerate() {
+            *o = u16::from(i[n]) << 2 | u16::from(low >> (2 * n) & 3);
+        }
+    }
+}
+
+fn raw12_row_to_raw8(row: &[u8], out: &mut [u8]) {
+    let full = out.len() / 2;
+    for (i, o) in row.chunks_exact(3).zip(out.chunks_exact_mut(2)) {
+        o.copy_from_slice(&i[..2]);
+    }
+    if out.len() % 2 == 1 {
+        out[full * 2] = row[full * 3];
+    }
+}
+
+fn raw12_row_to_raw16(row: &[u8], out: &mut [u16]) {
+    let full = out.len() / 2;
+    for (i, o) in row.chunks_exact(3).zip(out.chunks_exact_mut(2)) {
+        let low = i[2];
+        o[0] = u16::from(i[0]) << 4 | u16::from(low & 0xF);
+        o[1] = u16::from(i[1]) << 4 | u16::from(low >> 4);
+    }
+    if out.len() % 2 == 1 {
+        let i = &row[full * 3..];
+        out[full * 2] = u16::from(i[0]) << 4 | u16::from(i[1] & 0xF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack `values` of `bits` bits in the MIPI format, with the given stride and padded height.
+    fn pack(values: &[u16], bits: u32, width: usize, stride: usize, rows: usize) -> Vec<u8> {
+        let (bytes, pixels) = if bits == 10 { (5, 4) } else { (3, 2) };
+        let mut data = vec![0xEE; stride * rows];
+        for (y, row) in values.chunks(width).enumerate() {
+            let out = &mut data[y * stride..][..stride];
+            for (o, group) in out.chunks_mut(bytes).zip(row.chunks(pixels)) {
+                o[group.len()] = 0;
+                for (i, &v) in group.iter().enumerate() {
+                    o[i] = (v >> (bits - 8)) as u8;
+                    let low = v & ((1 << (bits - 8)) - 1);
+                    o[group.len()] |= (low << ((bits - 8) as usize * i)) as u8;
+                }
+            }
+        }
+        data
+    }
+
+    fn values(width: usize, height: usize, bits: u32) -> Vec<u16> {
+        (0..width * height)
+            .map(|i| (i as u16).wrapping_mul(337).wrapping_add(5) % (1 << bits))
+            .collect()
+    }
+
+    #[test]
+    fn raw10_round_trip() {
+        for width in 1..=11 {
+            for height in 1..=3 {
+                let expected = values(width, height, 10);
+                let stride = raw10_stride(width);
+                let input = pack(&expected, 10, width, stride, height);
+                let mut raw16 = vec![0; width * height];
+                unpack_raw10_to_raw16(&input, &mut raw16, width, height, stride).unwrap();
+                assert_eq!(raw16, expected, "width {}", width);
+                let mut raw8 = vec![0; width * height];
+                unpack_raw10_to_raw8(&input, &mut raw8, width, height, stride).unwrap();
+                let expected8: Vec<u8> = expected.iter().map(|&v| (v >> 2) as u8).collect();
+                assert_eq!(raw8, expected8, "width {}", width);
+            }
+        }
+    }
+
+    #[test]
+    fn raw12_round_trip() {
+        for width in 1..=11 {
+            for height in 1..=3 {
+                let expected = values(width, height, 12);
+                let stride = raw12_stride(width);
+                let input = pack(&expected, 12, width, stride, height);
+                let mut raw16 = vec![0; width * height];
+                unpack_raw12_to_raw16(&input, &mut raw16, width, height, stride).unwrap();
+                assert_eq!(raw16, expected, "width {}", width);
+                let mut raw8 = vec![0; width * height];
+                unpack_raw12_to_raw8(&input, &mut raw8, width, height, stride).unwrap();
+                let expected8: Vec<u8> = expected.iter().map(|&v| (v >> 4) as u8).collect();
+                assert_eq!(raw8, expected8, "width {}", width);
+            }
+        }
+    }
+
+    #[test]
+    fn strides() {
+        assert_eq!(raw10_stride(1), 32);
+        assert_eq!(raw10_stride(1920), 2400);
+        assert_eq!(raw10_stride(3280), 4128);
+        assert_eq!(raw12_stride(1), 32);
+        assert_eq!(raw12_stride(1920), 2880);
+        assert_eq!(raw12_stride(4056), 6112);
+    }
+
+    #[test]
+    fn padded_height() {
+        // A 1080 rows image in a buffer of 1088 rows, with garbage in the padding.
+        let (width, height) = (64, 1080);
+        let stride = raw10_stride(width);
+        let expected = values(width, height, 10);
+        let input = pack(&expected, 10, width, stride, 1088);
+        let mut output = vec![0; width * height];
+        unpack_raw10_to_raw16(&input, &mut output, width, height, stride).unwrap();
+        assert_eq!(output, expected);
+        let format = PixelFormat::Bggr10Packed;
+        assert_eq!(format.stride(width), Some(stride));
+        let mut output = vec![0; width * height];
+        unpack_to_raw16(&input, &mut output, width, height, format).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn too_short() {
+        let (width, height) = (8, 4);
+        let stride = raw10_stride(width);
+        let input = vec![0; stride * height];
+        let mut output = vec![0u16; width * height];
+        let kind = |r: Result<()>| r.unwrap_err().kind();
+
+        // The last row does not need its padding.
+        let short = &input[..stride * (height - 1) + 10];
+        unpack_raw10_to_raw16(short, &mut output, width, height, stride).unwrap();
+        let short = &input[..stride * (height - 1) + 9];
+        let r = unpack_raw10_to_raw16(short, &mut output, width, height, stride);
+        assert_eq!(kind(r), ErrorKind::InvalidArgument);
+
+        let r = unpack_raw10_to_raw16(&input, &mut output[1..], width, height, stride);
+        assert_eq!(kind(r), ErrorKind::InvalidArgument);
+
+        let r = unpack_raw10_to_raw16(&input, &mut output, width, height, 9);
+        assert_eq!(kind(r), ErrorKind::InvalidArgument);
+
+        let mut output8 = vec![0u8; width * height];
+        let r = unpack_raw12_to_raw8(&input[..12], &mut output8, width, height, 12);
+        assert_eq!(kind(r), ErrorKind::InvalidArgument);
+
+        let format = PixelFormat::Unknown(0);
+        let r = unpack_to_raw16(&input, &mut output, width, height, format);
+        assert_eq!(kind(r), ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,79 @@
+//! Encoded (H.264 or MJPEG) video output.
+
+use crate::{c, BufferFlags, Camera, OutputKind, OutputStream, Result, VideoEncoderConfig};
+
+/// A piece of encoded video, as given to the callback of [`Camera::start_video`].
+///
+/// A chunk is only valid during the callback.
+/// A single frame might be split over multiple chunks.
+pub struct VideoChunk<'a> {
+    buffer: &'a c::Buffer,
+}
+
+impl<'a> VideoChunk<'a> {
+    /// The encoded data.
+    pub fn data(&self) -> &'a [u8] {
+        unsafe { self.buffer.bytes() }
+    }
+
+    /// The presentation timestamp of the chunk.
+    ///
+    /// Returns `None` when the timestamp is set to `TIME_UNKNOWN`.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.buffer.timestamp()
+    }
+
+    /// The metadata flags of the chunk.
+    pub fn flags(&self) -> BufferFlags {
+        BufferFlags::from_bits_retain(self.buffer.flags)
+    }
+
+    /// The chunk is (part of) a keyframe.
+    pub fn is_keyframe(&self) -> bool {
+        self.flags().is_keyframe()
+    }
+
+    /// The chunk contains codec config data (e.g. SPS and PPS headers), not a frame.
+    pub fn is_config(&self) -> bool {
+        self.flags().is_config()
+    }
+
+    /// The chunk ends a NAL unit.
+    pub fn is_nal_end(&self) -> bool {
+        self.flags().contains(BufferFlags::NAL_END)
+    }
+
+    /// The chunk ends a frame.
+    pub fn is_frame_end(&self) -> bool {
+        self.flags().is_frame_end()
+    }
+
+    /// The data is known to be corrupted, or failed to be transmitted.
+    pub fn is_corrupted(&self) -> bool {
+        self.flags().is_corrupted()
+    }
+
+    /// Access to the raw [`Buffer`][c::Buffer] structure.
+    pub fn raw_buffer(&self) -> &'a c::Buffer {
+        self.buffer
+    }
+}
+
+impl Camera {
+    /// Start encoding video, calling `callback` for every piece of encoded data.
+    ///
+    /// The callback is called from another thread.
+    /// Returns an error of kind [`Busy`][crate::ErrorKind::Busy] if video output is already running.
+    pub fn start_video<F>(
+        &mut self,
+        config: VideoEncoderConfig,
+        mut callback: F,
+    ) -> Result<OutputStream<'_>>
+    where
+        F: FnMut(VideoChunk) + Send + 'static,
+    {
+        self.start_output(OutputKind::Video, Some(config.into()), move |buffer| {
+            callback(VideoChunk { buffer })
+        })
+    }
+}
@@ -0,0 +1,134 @@
+//! YUV (I420) frame output.
+
+use crate::{align_up, c, BufferFlags, Camera, Format, OutputKind, OutputStream, Result};
+use std::ops::Range;
+
+/// A YUV frame in I420 layout, as given to the callback of [`Camera::start_yuv_stream`].
+///
+/// The frame consists of a full resolution Y plane, followed by a U and a V
+/// plane at half resolution in both directions. Rows are padded to a multiple
+/// of 32 pixels, and the height is padded to a multiple of 16 rows.
+///
+/// A frame is only valid during the callback.
+pub struct YuvFrame<'a> {
+    buffer: &'a c::Buffer,
+    format: &'a Format,
+}
+
+impl<'a> YuvFrame<'a> {
+    /// All data of the frame, including padding.
+    pub fn data(&self) -> &'a [u8] {
+        unsafe { self.buffer.bytes() }
+    }
+
+    /// The Y (luma) plane, including padding.
+    pub fn y(&self) -> &'a [u8] {
+        self.plane(0..self.y_size())
+    }
+
+    /// The U (Cb) plane, including padding.
+    pub fn u(&self) -> &'a [u8] {
+        let start = self.y_size();
+        self.plane(start..start + self.uv_size())
+    }
+
+    /// The V (Cr) plane, including padding.
+    pub fn v(&self) -> &'a [u8] {
+        let start = self.y_size() + self.uv_size();
+        self.plane(start..start + self.uv_size())
+    }
+
+    /// The number of bytes between the start of two rows in the Y plane.
+    pub fn y_stride(&self) -> usize {
+        align_up(self.width(), 32)
+    }
+
+    /// The number of bytes between the start of two rows in the U and V planes.
+    pub fn uv_stride(&self) -> usize {
+        self.y_stride() / 2
+    }
+
+    /// The number of rows in the Y plane, including padding.
+    pub fn padded_height(&self) -> usize {
+        align_up(self.height(), 16)
+    }
+
+    /// The width of the frame, in pixels.
+    pub fn width(&self) -> usize {
+        self.format.width as usize
+    }
+
+    /// The height of the frame, in pixels.
+    pub fn height(&self) -> usize {
+        self.format.height as usize
+    }
+
+    /// The presentation timestamp of the frame.
+    ///
+    /// Returns `None` when the timestamp is set to `TIME_UNKNOWN`.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.buffer.timestamp()
+    }
+
+    /// The metadata flags of the frame.
+    pub fn flags(&self) -> BufferFlags {
+        BufferFlags::from_bits_retain(self.buffer.flags)
+    }
+
+    /// The buffer ends a frame. If not set, the frame is incomplete.
+    pub fn is_frame_end(&self) -> bool {
+        self.flags().is_frame_end()
+    }
+
+    /// The data is known to be corrupted, or failed to be transmitted.
+    pub fn is_corrupted(&self) -> bool {
+        self.flags().is_corrupted()
+    }
+
+    /// The format of the camera at the time the stream was started.
+    pub fn format(&self) -> &'a Format {
+        self.format
+    }
+
+    /// Access to the raw [`Buffer`][c::Buffer] structure.
+    pub fn raw_buffer(&self) -> &'a c::Buffer {
+        self.buffer
+    }
+
+    fn y_size(&self) -> usize {
+        self.y_stride() * self.padded_height()
+    }
+
+    fn uv_size(&self) -> usize {
+        self.uv_stride() * (self.padded_height() / 2)
+    }
+
+    /// The given range of the data, truncated if the buffer is too short.
+    fn plane(&self, range: Range<usize>) -> &'a [u8] {
+        let data = self.data();
+        let end = range.end.min(data.len());
+        &data[range.start.min(end)..end]
+    }
+}
+
+impl Camera {
+    /// Start streaming YUV frames, calling `callback` for every frame.
+    ///
+    /// Every frame is tagged with the [`Format`] of the camera at the time this is called.
+    /// The resolution and mode can't be changed while streaming.
+    ///
+    /// The callback is called from another thread.
+    /// Returns an error of kind [`Busy`][crate::ErrorKind::Busy] if YUV output is already running.
+    pub fn start_yuv_stream<F>(&mut self, mut callback: F) -> Result<OutputStream<'_>>
+    where
+        F: FnMut(YuvFrame) + Send + 'static,
+    {
+        let format = self.get_format()?;
+        self.start_output(OutputKind::Yuv, None, move |buffer| {
+            callback(YuvFrame {
+                buffer,
+                format: &format,
+            })
+        })
+    }
+}